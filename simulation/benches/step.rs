@@ -0,0 +1,77 @@
+//! Per-step cost of the automata and of the image and height map writes
+//! the app does every frame, run with `cargo bench -p simulation`.
+
+use std::time::Instant;
+
+use simulation::{render, Automaton, Lenia, LifeRule, Simulation};
+
+const STEPS: u32 = 20;
+
+fn time(name: &str, mut run: impl FnMut()) {
+    // The first run builds kernels and buffers.
+    run();
+    let start = Instant::now();
+    for _ in 0..STEPS {
+        run();
+    }
+    let per_step = start.elapsed() / STEPS;
+    println!(
+        "{:32} {:>10.3} ms/step",
+        name,
+        per_step.as_secs_f64() * 1000.0
+    );
+}
+
+fn bench(name: &str, mut simulation: Simulation) {
+    time(name, || simulation.step());
+}
+
+// The writes the app does after every step, into buffers kept between frames.
+fn bench_display(size: usize) {
+    let simulation = sized(size, size, Automaton::ReactionDiffusion);
+    let grid = &simulation.grid;
+    let colors = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+    let mut pixels = vec![[0u8; 4]; size * size];
+    time(&format!("image {0}x{0}", size), || {
+        render::write_colors(grid, &colors, &mut pixels, |r, g, b| [r, g, b, 255])
+    });
+    let mut vertices = vec![[0.0f32; 3]; size * size];
+    let mut normals = vec![[0.0f32; 3]; size * size];
+    time(&format!("height map {0}x{0}", size), || {
+        render::write_heights(&mut vertices, grid, None);
+        render::write_normals(
+            &mut normals,
+            &vertices,
+            size,
+            size,
+            simulation.boundary,
+            0.04,
+        );
+    });
+}
+
+fn sized(width: usize, height: usize, automaton: Automaton) -> Simulation {
+    let mut simulation = Simulation::default();
+    simulation.resize(width, height);
+    simulation.set_automaton(automaton);
+    simulation.reset();
+    simulation
+}
+
+fn main() {
+    for size in [160, 512] {
+        bench(
+            &format!("reaction-diffusion {0}x{0}", size),
+            sized(size, size, Automaton::ReactionDiffusion),
+        );
+    }
+    bench(
+        "lenia 256x256",
+        sized(256, 256, Automaton::Lenia(Lenia::default())),
+    );
+    bench(
+        "life 512x512",
+        sized(512, 512, Automaton::Life(LifeRule::default())),
+    );
+    bench_display(512);
+}
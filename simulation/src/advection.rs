@@ -0,0 +1,311 @@
+use std::f32::consts::TAU;
+
+use super::{
+    boundary::Boundary,
+    domain::{CellKind, Domain},
+    fluid::Fluid,
+    grid::Grid,
+    parallel,
+};
+
+// Offsets of the four faces of a cell.
+pub(crate) const FACES: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+
+/// Prescribed velocity field transporting every channel, in cells per unit
+/// of time.
+#[derive(Clone)]
+pub enum Flow {
+    Still,
+    Uniform {
+        velocity: [f32; 2],
+    },
+    /// Rotation about the centre of the grid, fading out beyond `radius`.
+    Vortex {
+        angular_speed: f32,
+        radius: f32,
+    },
+    /// Horizontal flow varying sinusoidally with the row, periodic in both
+    /// directions.
+    Shear {
+        speed: f32,
+    },
+    /// Velocity read from the first two channels of `field`, each between -1
+    /// and 1, stretched over the grid and scaled by `scale`.
+    Image {
+        field: Grid,
+        scale: f32,
+    },
+    /// Incompressible fluid moving on its own, stirred by the brush.
+    Fluid(Box<Fluid>),
+}
+
+impl Default for Flow {
+    fn default() -> Self {
+        Self::Still
+    }
+}
+
+impl Flow {
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Still => "None",
+            Self::Uniform { .. } => "Uniform drift",
+            Self::Vortex { .. } => "Vortex",
+            Self::Shear { .. } => "Shear",
+            Self::Image { .. } => "Vector image",
+            Self::Fluid(_) => "Fluid",
+        }
+    }
+
+    pub fn is_still(&self) -> bool {
+        matches!(self, Self::Still)
+    }
+
+    pub fn velocity(&self, x: usize, y: usize, width: usize, height: usize) -> [f32; 2] {
+        let u = x as f32 + 0.5 - 0.5 * width as f32;
+        let v = y as f32 + 0.5 - 0.5 * height as f32;
+        match self {
+            Self::Still => [0.0, 0.0],
+            Self::Uniform { velocity } => *velocity,
+            Self::Vortex {
+                angular_speed,
+                radius,
+            } => {
+                let falloff = (-(u * u + v * v) / (radius * radius)).exp();
+                [-angular_speed * v * falloff, angular_speed * u * falloff]
+            }
+            Self::Shear { speed } => [speed * (TAU * (y as f32 + 0.5) / height as f32).sin(), 0.0],
+            Self::Image { field, scale } => [
+                scale * field.sample(x, y, width, height, 0),
+                scale * field.sample(x, y, width, height, 1),
+            ],
+            Self::Fluid(fluid) => fluid.velocity(x, y),
+        }
+    }
+
+    /// Upper bound of `|vx| + |vy|` over the grid.
+    pub fn max_speed(&self) -> f32 {
+        match self {
+            Self::Still => 0.0,
+            Self::Uniform { velocity } => velocity[0].abs() + velocity[1].abs(),
+            // |v| peaks at radius / sqrt(2), |vx| + |vy| is at most sqrt(2) |v|.
+            Self::Vortex {
+                angular_speed,
+                radius,
+            } => (angular_speed * radius).abs() * (-0.5f32).exp(),
+            Self::Shear { speed } => speed.abs(),
+            Self::Image { field, scale } => {
+                let max = |channel| {
+                    field
+                        .channel(channel)
+                        .iter()
+                        .fold(0.0f32, |max, value| max.max(value.abs()))
+                };
+                scale.abs() * (max(0) + max(1))
+            }
+            Self::Fluid(fluid) => fluid.max_speed(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum AdvectionScheme {
+    /// First order upwind differences, added to the rates of the integrator.
+    /// Stable while no cell moves further than one cell per step.
+    Upwind,
+    /// Traces every cell back along the flow after each step and
+    /// interpolates there, stable for any time step.
+    SemiLagrangian,
+}
+
+impl Default for AdvectionScheme {
+    fn default() -> Self {
+        Self::SemiLagrangian
+    }
+}
+
+impl AdvectionScheme {
+    pub const PRESETS: [Self; 2] = [Self::Upwind, Self::SemiLagrangian];
+
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Upwind => "Upwind",
+            Self::SemiLagrangian => "Semi-Lagrangian",
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Advection {
+    pub flow: Flow,
+    pub scheme: AdvectionScheme,
+    // Velocity of every cell, row by row, kept between steps.
+    velocity: Vec<[f32; 2]>,
+}
+
+impl Advection {
+    /// Evaluates the flow on every cell, called once per step of length
+    /// `dt`, which a fluid moves on by first. Walls of `domain` hold still.
+    pub(crate) fn update_velocity(
+        &mut self,
+        width: usize,
+        height: usize,
+        dt: f32,
+        domain: Option<&Domain>,
+    ) {
+        if let Flow::Fluid(fluid) = &mut self.flow {
+            fluid.fit(width, height);
+            fluid.step(dt);
+            if let Some(domain) = domain {
+                fluid.stop_at_walls(domain);
+            }
+        }
+        self.velocity.clear();
+        for y in 0..height {
+            self.velocity.extend((0..width).map(
+                |x| match domain.map(|domain| domain.kind(x, y)) {
+                    Some(CellKind::Wall) => [0.0, 0.0],
+                    _ => self.flow.velocity(x, y, width, height),
+                },
+            ));
+        }
+    }
+
+    /// Cell velocities for the upwind term, `None` when another scheme
+    /// transports the field or nothing moves.
+    pub(crate) fn upwind_velocity(&self) -> Option<&[[f32; 2]]> {
+        (self.scheme == AdvectionScheme::Upwind && !self.flow.is_still())
+            .then_some(self.velocity.as_slice())
+    }
+
+    /// Moves the field along the flow for `dt` with the semi-Lagrangian
+    /// scheme, if selected, using `traced` as the buffer for the new field.
+    pub(crate) fn transport(
+        &self,
+        grid: &mut Grid,
+        traced: &mut Grid,
+        dt: f32,
+        boundary: Boundary,
+        domain: Option<&Domain>,
+    ) {
+        if self.scheme != AdvectionScheme::SemiLagrangian || self.flow.is_still() {
+            return;
+        }
+        let (width, height) = (grid.width(), grid.height());
+        if !traced.same_shape(grid) {
+            *traced = Grid::new(width, height, grid.channels());
+        }
+        if let Some(domain) = domain {
+            self.transport_around_walls(grid, traced, dt, boundary, domain);
+            std::mem::swap(grid, traced);
+            return;
+        }
+        let (source, velocity) = (&*grid, &self.velocity);
+        let bands = traced.bands_mut(parallel::band_rows(height));
+        parallel::for_each_band(bands, |mut band| {
+            for row in 0..band.rows {
+                let y = band.first_row + row;
+                for x in 0..width {
+                    let [vx, vy] = velocity[y * width + x];
+                    let (px, py) = (x as f32 - vx * dt, y as f32 - vy * dt);
+                    let (x0, y0) = (px.floor(), py.floor());
+                    let (fx, fy) = (px - x0, py - y0);
+                    let (x0, y0) = (x0 as i32, y0 as i32);
+                    for (channel, output) in band.channels.iter_mut().enumerate() {
+                        let value = |x, y| boundary.value(source, x, y, channel);
+                        let top = value(x0, y0) * (1.0 - fx) + value(x0 + 1, y0) * fx;
+                        let bottom = value(x0, y0 + 1) * (1.0 - fx) + value(x0 + 1, y0 + 1) * fx;
+                        output[row * width + x] = top * (1.0 - fy) + bottom * fy;
+                    }
+                }
+            }
+        });
+        std::mem::swap(grid, traced);
+    }
+
+    // Tracing back would pull values out of walls, so instead every cell
+    // moves forward until just before a wall and spreads its content over
+    // the four cells around where it lands. Shares that would land in a wall
+    // or past a closed edge stay in the cell, so nothing is lost or made.
+    fn transport_around_walls(
+        &self,
+        source: &Grid,
+        traced: &mut Grid,
+        dt: f32,
+        boundary: Boundary,
+        domain: &Domain,
+    ) {
+        let (width, height) = (source.width(), source.height());
+        let open = |x: f32, y: f32| {
+            boundary
+                .wrap(x as i32, y as i32, width, height)
+                .filter(|(x, y)| domain.kind(*x, *y) != CellKind::Wall)
+        };
+        for channel in 0..traced.channels() {
+            traced.channel_mut(channel).fill(0.0);
+        }
+        for y in 0..height {
+            for x in 0..width {
+                if domain.kind(x, y) == CellKind::Wall {
+                    continue;
+                }
+                let [vx, vy] = self.velocity[y * width + x];
+                let (dx, dy) = (vx * dt, vy * dt);
+                let steps = dx.abs().max(dy.abs()).ceil().max(1.0) as usize;
+                let (mut px, mut py) = (x as f32, y as f32);
+                for step in 1..=steps {
+                    let t = step as f32 / steps as f32;
+                    let (qx, qy) = (x as f32 + t * dx, y as f32 + t * dy);
+                    if open(qx.round(), qy.round()).is_none() {
+                        break;
+                    }
+                    (px, py) = (qx, qy);
+                }
+                let (x0, y0) = (px.floor(), py.floor());
+                let (fx, fy) = (px - x0, py - y0);
+                let corners = [
+                    (x0, y0, (1.0 - fx) * (1.0 - fy)),
+                    (x0 + 1.0, y0, fx * (1.0 - fy)),
+                    (x0, y0 + 1.0, (1.0 - fx) * fy),
+                    (x0 + 1.0, y0 + 1.0, fx * fy),
+                ];
+                for (cx, cy, weight) in corners {
+                    let (tx, ty) = open(cx, cy).unwrap_or((x, y));
+                    for channel in 0..source.channels() {
+                        let value =
+                            traced.get(tx, ty, channel) + weight * source.get(x, y, channel);
+                        traced.set(tx, ty, channel, value);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Upwind approximation of `-div(v c)` at a cell holding `center` and moving
+/// at `velocity`. `neighbour` gives the value and velocity of the neighbour
+/// at offset `(dx, dy)`, or `None` across a wall, which nothing crosses.
+/// Every face carries its mean velocity times the value on the side it
+/// leaves, so whatever one cell loses its neighbour gains.
+pub(crate) fn upwind(
+    center: f32,
+    velocity: [f32; 2],
+    neighbour: impl Fn(i32, i32) -> Option<(f32, [f32; 2])>,
+) -> f32 {
+    FACES
+        .iter()
+        .map(|&(dx, dy)| {
+            let Some((value, other)) = neighbour(dx, dy) else {
+                return 0.0;
+            };
+            let axis = if dx != 0 { 0 } else { 1 };
+            // Positive out of the cell.
+            let outward = 0.5 * (velocity[axis] + other[axis]) * (dx + dy) as f32;
+            if outward > 0.0 {
+                -outward * center
+            } else {
+                -outward * value
+            }
+        })
+        .sum()
+}
@@ -0,0 +1,158 @@
+use super::grid::Grid;
+
+/// How the grid continues past its edges.
+///
+/// Stencils, kernels and the brush all read cells outside the grid through
+/// the boundary, so every automaton sees the same topology.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Boundary {
+    /// Opposite edges are glued together (torus).
+    Periodic,
+    /// Zero flux: the cells beyond an edge copy the edge cell.
+    Neumann,
+    /// The cells beyond an edge hold a fixed value.
+    Dirichlet(f32),
+    /// The grid is mirrored about its edge cells.
+    Reflective,
+    /// Left and right edges are glued with a half twist, top and bottom
+    /// edges are zero flux.
+    Mobius,
+    /// Left and right edges are glued with a half twist, top and bottom
+    /// edges are glued together.
+    KleinBottle,
+}
+
+/// Where the value of a position outside the grid comes from.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Neighbour {
+    Cell(usize, usize),
+    Value(f32),
+}
+
+impl Boundary {
+    pub const PRESETS: [Boundary; 6] = [
+        Boundary::Periodic,
+        Boundary::Neumann,
+        Boundary::Dirichlet(0.0),
+        Boundary::Reflective,
+        Boundary::Mobius,
+        Boundary::KleinBottle,
+    ];
+
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Periodic => "Periodic",
+            Self::Neumann => "Zero flux (Neumann)",
+            Self::Dirichlet(_) => "Fixed value (Dirichlet)",
+            Self::Reflective => "Reflective",
+            Self::Mobius => "Möbius strip",
+            Self::KleinBottle => "Klein bottle",
+        }
+    }
+
+    pub fn is_periodic(&self) -> bool {
+        *self == Self::Periodic
+    }
+
+    /// Cell the position `(x, y)` is glued to by the topology, or `None` if
+    /// it lies past an open edge.
+    pub fn wrap(&self, x: i32, y: i32, width: usize, height: usize) -> Option<(usize, usize)> {
+        let (w, h) = (width as i32, height as i32);
+        let inside = (0..w).contains(&x) && (0..h).contains(&y);
+        let (x, y) = match self {
+            Self::Periodic => (x.rem_euclid(w), y.rem_euclid(h)),
+            Self::Neumann | Self::Dirichlet(_) | Self::Reflective if inside => (x, y),
+            Self::Neumann | Self::Dirichlet(_) | Self::Reflective => return None,
+            Self::Mobius if (0..h).contains(&y) => twist(x, y, w, h),
+            Self::Mobius => return None,
+            Self::KleinBottle => twist(x, y.rem_euclid(h), w, h),
+        };
+        Some((x as usize, y as usize))
+    }
+
+    /// Source of the value at any position, inside the grid or not.
+    pub fn neighbour(&self, x: i32, y: i32, width: usize, height: usize) -> Neighbour {
+        let (w, h) = (width as i32, height as i32);
+        let (x, y) = match self {
+            Self::Periodic | Self::KleinBottle => {
+                let (x, y) = self.wrap(x, y, width, height).unwrap();
+                return Neighbour::Cell(x, y);
+            }
+            Self::Neumann => (mirror(x, w, false), mirror(y, h, false)),
+            Self::Reflective => (mirror(x, w, true), mirror(y, h, true)),
+            Self::Dirichlet(value) => {
+                if (0..w).contains(&x) && (0..h).contains(&y) {
+                    (x, y)
+                } else {
+                    return Neighbour::Value(*value);
+                }
+            }
+            Self::Mobius => twist(x, mirror(y, h, false), w, h),
+        };
+        Neighbour::Cell(x as usize, y as usize)
+    }
+
+    pub fn value(&self, grid: &Grid, x: i32, y: i32, channel: usize) -> f32 {
+        let (width, height) = (grid.width(), grid.height());
+        if (0..width as i32).contains(&x) && (0..height as i32).contains(&y) {
+            return grid.get(x as usize, y as usize, channel);
+        }
+        match self.neighbour(x, y, width, height) {
+            Neighbour::Cell(x, y) => grid.get(x, y, channel),
+            Neighbour::Value(value) => value,
+        }
+    }
+
+    pub fn sum_neighbours(
+        &self,
+        grid: &Grid,
+        x: i32,
+        y: i32,
+        channel: usize,
+        taps: &[(i32, i32, f32)],
+    ) -> f32 {
+        taps.iter()
+            .map(|(dx, dy, weight)| self.value(grid, x + dx, y + dy, channel) * weight)
+            .sum()
+    }
+}
+
+impl Default for Boundary {
+    fn default() -> Self {
+        Self::Periodic
+    }
+}
+
+// Wraps x around the width, flipping y on every odd crossing.
+fn twist(x: i32, y: i32, width: i32, height: i32) -> (i32, i32) {
+    if x.div_euclid(width) % 2 == 0 {
+        (x.rem_euclid(width), y)
+    } else {
+        (x.rem_euclid(width), height - 1 - y)
+    }
+}
+
+// Mirrors a coordinate back into 0..size, either about the edge cell itself
+// (whole sample) or about the face beyond it, repeating the edge cell.
+fn mirror(position: i32, size: i32, whole_sample: bool) -> i32 {
+    if whole_sample {
+        if size == 1 {
+            return 0;
+        }
+        let period = 2 * size - 2;
+        let position = position.rem_euclid(period);
+        if position >= size {
+            period - position
+        } else {
+            position
+        }
+    } else {
+        let period = 2 * size;
+        let position = position.rem_euclid(period);
+        if position >= size {
+            period - 1 - position
+        } else {
+            position
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use rand::{distributions::Distribution, Rng};
+
+use super::{noise::Noise, reaction::ParameterSpec};
+
+#[derive(Default)]
+pub struct ChannelParameters {
+    pub diffusion_coefficient: f32,
+    /// Rate at which the Laplacian of every other channel, by index, drives
+    /// this one. The entry of the channel itself is unused, missing entries
+    /// are zero.
+    pub cross_diffusion: Vec<f32>,
+    pub values: Vec<f32>,
+    pub noise: Noise,
+}
+
+impl Clone for ChannelParameters {
+    fn clone(&self) -> Self {
+        Self {
+            diffusion_coefficient: self.diffusion_coefficient,
+            cross_diffusion: self.cross_diffusion.clone(),
+            values: self.values.clone(),
+            noise: self.noise,
+        }
+    }
+
+    // Reuses the vectors, the rate evaluation refreshes its copies every step.
+    fn clone_from(&mut self, source: &Self) {
+        self.diffusion_coefficient = source.diffusion_coefficient;
+        self.cross_diffusion.clone_from(&source.cross_diffusion);
+        self.values.clone_from(&source.values);
+        self.noise = source.noise;
+    }
+}
+
+impl ChannelParameters {
+    pub fn from_specs(diffusion: &ParameterSpec, specs: &[ParameterSpec]) -> Self {
+        Self {
+            diffusion_coefficient: diffusion.default,
+            cross_diffusion: Vec::new(),
+            values: specs.iter().map(|spec| spec.default).collect(),
+            noise: Noise::default(),
+        }
+    }
+
+    pub fn random(diffusion: &ParameterSpec, specs: &[ParameterSpec], rng: &mut impl Rng) -> Self {
+        Self {
+            diffusion_coefficient: diffusion.sample(rng),
+            cross_diffusion: Vec::new(),
+            values: specs.iter().map(|spec| spec.sample(rng)).collect(),
+            noise: Noise::default(),
+        }
+    }
+
+    /// Coefficient of the Laplacian of channel `source` in the diffusion of
+    /// this one, which is channel `channel`.
+    pub fn diffusion_from(&self, channel: usize, source: usize) -> f32 {
+        if source == channel {
+            self.diffusion_coefficient
+        } else {
+            self.cross_diffusion.get(source).copied().unwrap_or(0.0)
+        }
+    }
+
+    /// Diffusive rate of change of channel `channel` given the Laplacian of
+    /// every channel.
+    pub(crate) fn diffusion(&self, channel: usize, laplacians: &[f32]) -> f32 {
+        laplacians
+            .iter()
+            .enumerate()
+            .map(|(source, laplacian)| self.diffusion_from(channel, source) * laplacian)
+            .sum()
+    }
+}
+
+impl ParameterSpec {
+    pub fn sample(&self, rng: &mut impl Rng) -> f32 {
+        rand::distributions::Uniform::new_inclusive(*self.range.start(), *self.range.end())
+            .sample(rng)
+    }
+}
@@ -0,0 +1,82 @@
+use super::advection::FACES;
+
+/// Keller-Segel chemotaxis: the `cells` channel drifts up the gradient of the
+/// `attractant` channel,
+/// `d cells / dt = -div(sensitivity * cells * grad attractant / (1 + saturation * attractant))`.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Chemotaxis {
+    pub cells: usize,
+    pub attractant: usize,
+    pub sensitivity: f32,
+    /// Receptor saturation, how much high attractant levels blunt the
+    /// response.
+    pub saturation: f32,
+}
+
+impl Default for Chemotaxis {
+    fn default() -> Self {
+        Self {
+            cells: 0,
+            attractant: 1,
+            sensitivity: 1.0,
+            saturation: 0.0,
+        }
+    }
+}
+
+impl Chemotaxis {
+    /// Whether both channels exist in a field of `channels` channels.
+    pub fn fits(&self, channels: usize) -> bool {
+        self.cells < channels && self.attractant < channels
+    }
+
+    fn sensitivity_at(&self, attractant: f32) -> f32 {
+        self.sensitivity / (1.0 + self.saturation * attractant.max(0.0))
+    }
+
+    /// Net inflow of cells into a cell holding `cells` and `attractant`,
+    /// reading channel `channel` of the neighbour at offset `(dx, dy)`
+    /// through `neighbour`. Every face carries the cells of the side they
+    /// leave, so whatever one cell loses its neighbour gains and the cells
+    /// stay non-negative.
+    pub(crate) fn rate(
+        &self,
+        cells: f32,
+        attractant: f32,
+        neighbour: impl Fn(usize, i32, i32) -> f32,
+    ) -> f32 {
+        FACES
+            .iter()
+            .map(|&(dx, dy)| {
+                let other_attractant = neighbour(self.attractant, dx, dy);
+                // Positive towards the neighbour.
+                let velocity = self.sensitivity_at(0.5 * (attractant + other_attractant))
+                    * (other_attractant - attractant);
+                if velocity > 0.0 {
+                    -velocity * cells
+                } else {
+                    -velocity * neighbour(self.cells, dx, dy)
+                }
+            })
+            .sum()
+    }
+
+    /// Sum of the attractant differences across the faces of a cell holding
+    /// `attractant`, read through `neighbour` like in `rate`.
+    pub(crate) fn steepness(
+        &self,
+        attractant: f32,
+        neighbour: impl Fn(usize, i32, i32) -> f32,
+    ) -> f32 {
+        FACES
+            .iter()
+            .map(|&(dx, dy)| (neighbour(self.attractant, dx, dy) - attractant).abs())
+            .sum()
+    }
+
+    /// Upper bound of the rate at which a cell can empty, relative to its
+    /// content, where no cell is steeper than `steepness`.
+    pub(crate) fn max_rate(&self, steepness: f32) -> f32 {
+        self.sensitivity.abs() * steepness
+    }
+}
@@ -0,0 +1,100 @@
+use std::sync::Arc;
+
+use rustfft::{num_complex::Complex, Fft, FftPlanner};
+
+/// Periodic 2D convolution with a fixed kernel through the FFT, for kernels
+/// too large to be summed cell by cell.
+#[derive(Clone)]
+pub struct FftConvolution {
+    fft: Fft2d,
+    kernel_spectrum: Vec<Complex<f32>>,
+    buffer: Vec<Complex<f32>>,
+}
+
+impl FftConvolution {
+    pub fn new(width: usize, height: usize, taps: &[(i32, i32, f32)]) -> Self {
+        let mut fft = Fft2d::new(width, height);
+        let mut kernel = vec![Complex::default(); width * height];
+        for (dx, dy, weight) in taps {
+            let x = dx.rem_euclid(width as i32) as usize;
+            let y = dy.rem_euclid(height as i32) as usize;
+            kernel[y * width + x].re += weight;
+        }
+        fft.process(&mut kernel, true);
+        Self {
+            fft,
+            kernel_spectrum: kernel,
+            buffer: vec![Complex::default(); width * height],
+        }
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        (self.fft.width, self.fft.height)
+    }
+
+    /// Writes `sum_d weight(d) * input(x + d)` for every cell into `output`.
+    pub fn apply(&mut self, input: &[f32], output: &mut [f32]) {
+        for (value, input) in self.buffer.iter_mut().zip(input) {
+            *value = Complex::new(*input, 0.0);
+        }
+        self.fft.process(&mut self.buffer, true);
+        // The kernel was transformed as a convolution, taking the conjugate
+        // turns it into the correlation the stencils use.
+        for (value, kernel) in self.buffer.iter_mut().zip(&self.kernel_spectrum) {
+            *value *= kernel.conj();
+        }
+        self.fft.process(&mut self.buffer, false);
+        let scale = 1.0 / (self.fft.width * self.fft.height) as f32;
+        for (output, value) in output.iter_mut().zip(&self.buffer) {
+            *output = value.re * scale;
+        }
+    }
+}
+
+/// Unnormalized 2D FFT of row-major buffers of a fixed size.
+#[derive(Clone)]
+pub(crate) struct Fft2d {
+    pub width: usize,
+    pub height: usize,
+    row_forward: Arc<dyn Fft<f32>>,
+    row_inverse: Arc<dyn Fft<f32>>,
+    column_forward: Arc<dyn Fft<f32>>,
+    column_inverse: Arc<dyn Fft<f32>>,
+    transposed: Vec<Complex<f32>>,
+}
+
+impl Fft2d {
+    pub fn new(width: usize, height: usize) -> Self {
+        let mut planner = FftPlanner::new();
+        Self {
+            width,
+            height,
+            row_forward: planner.plan_fft_forward(width),
+            row_inverse: planner.plan_fft_inverse(width),
+            column_forward: planner.plan_fft_forward(height),
+            column_inverse: planner.plan_fft_inverse(height),
+            transposed: vec![Complex::default(); width * height],
+        }
+    }
+
+    /// Transforms `buffer` in place, the inverse is scaled by the cell count.
+    pub fn process(&mut self, buffer: &mut [Complex<f32>], forward: bool) {
+        let (rows, columns) = if forward {
+            (&self.row_forward, &self.column_forward)
+        } else {
+            (&self.row_inverse, &self.column_inverse)
+        };
+        rows.process(buffer);
+        transpose(buffer, &mut self.transposed, self.width, self.height);
+        columns.process(&mut self.transposed);
+        transpose(&self.transposed, buffer, self.height, self.width);
+    }
+}
+
+fn transpose(input: &[Complex<f32>], output: &mut [Complex<f32>], width: usize, height: usize) {
+    for y in 0..height {
+        for x in 0..width {
+            output[x * height + y] = input[y * width + x];
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use super::{
+    boundary::{Boundary, Neighbour},
+    grid::Grid,
+};
+
+/// Role of a cell in the reaction-diffusion domain.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CellKind {
+    Open,
+    /// Inert obstacle, nothing diffuses across its faces.
+    Wall,
+    /// Source or sink holding a fixed concentration of every channel.
+    Fixed,
+}
+
+impl Default for CellKind {
+    fn default() -> Self {
+        Self::Open
+    }
+}
+
+impl CellKind {
+    pub const PRESETS: [Self; 3] = [Self::Open, Self::Wall, Self::Fixed];
+
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Open => "Open",
+            Self::Wall => "Wall",
+            Self::Fixed => "Source / sink",
+        }
+    }
+}
+
+/// Mask shaping the domain the reaction-diffusion equations live on.
+#[derive(Clone, Default)]
+pub struct Domain {
+    width: usize,
+    height: usize,
+    kinds: Vec<CellKind>,
+    // Concentrations held by the fixed cells.
+    values: Grid,
+}
+
+impl Domain {
+    /// Reads a mask from an RGB image stretched over a `width` x `height`
+    /// grid: black cells are walls, red ones sources fixed at 1, blue ones
+    /// sinks fixed at 0, everything else is open.
+    pub fn from_image(image: &Grid, width: usize, height: usize, channels: usize) -> Self {
+        let mut domain = Self::default();
+        domain.fit(width, height, channels);
+        for y in 0..height {
+            for x in 0..width {
+                let [red, green, blue] =
+                    [0, 1, 2].map(|channel| image.sample(x, y, width, height, channel));
+                let (kind, value) = if red.max(green).max(blue) < 0.25 {
+                    (CellKind::Wall, 0.0)
+                } else if red > 0.5 && green.max(blue) < 0.5 {
+                    (CellKind::Fixed, 1.0)
+                } else if blue > 0.5 && red.max(green) < 0.5 {
+                    (CellKind::Fixed, 0.0)
+                } else {
+                    (CellKind::Open, 0.0)
+                };
+                domain.kinds[y * width + x] = kind;
+                for channel in 0..channels {
+                    domain.values.set(x, y, channel, value);
+                }
+            }
+        }
+        domain
+    }
+
+    /// Whether every cell is open, so the mask changes nothing.
+    pub fn is_open(&self) -> bool {
+        self.kinds.iter().all(|kind| *kind == CellKind::Open)
+    }
+
+    pub fn kind(&self, x: usize, y: usize) -> CellKind {
+        self.kinds
+            .get(y * self.width + x)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn clear(&mut self) {
+        self.kinds.fill(CellKind::Open);
+    }
+
+    /// Marks the cells within `radius` of `(center_x, center_y)` as `kind`,
+    /// fixed cells holding `values`.
+    pub(crate) fn paint(
+        &mut self,
+        center_x: i32,
+        center_y: i32,
+        radius: usize,
+        kind: CellKind,
+        values: &[f32],
+        boundary: Boundary,
+    ) {
+        let radius = radius as i32;
+        for dy in -radius..=radius {
+            for dx in -radius..=radius {
+                if dx * dx + dy * dy > radius * radius {
+                    continue;
+                }
+                let Some((x, y)) =
+                    boundary.wrap(center_x + dx, center_y + dy, self.width, self.height)
+                else {
+                    continue;
+                };
+                self.kinds[y * self.width + x] = kind;
+                for (channel, value) in values.iter().enumerate().take(self.values.channels()) {
+                    self.values.set(x, y, channel, *value);
+                }
+            }
+        }
+    }
+
+    /// Adapts the mask to the shape of the field, stretching it over a new
+    /// resolution.
+    pub(crate) fn fit(&mut self, width: usize, height: usize, channels: usize) {
+        if (self.width, self.height) != (width, height) {
+            let kinds = (0..width * height)
+                .map(|index| {
+                    if self.kinds.is_empty() {
+                        return CellKind::Open;
+                    }
+                    let x = (index % width) * self.width / width;
+                    let y = (index / width) * self.height / height;
+                    self.kinds[y * self.width + x]
+                })
+                .collect();
+            self.kinds = kinds;
+            self.values = if self.values.channels() == 0 {
+                Grid::new(width, height, channels)
+            } else {
+                self.values.resampled(width, height)
+            };
+            self.width = width;
+            self.height = height;
+        }
+        if self.values.channels() != channels {
+            let mut values = Grid::new(width, height, channels);
+            for channel in 0..channels.min(self.values.channels()) {
+                values
+                    .channel_mut(channel)
+                    .copy_from_slice(self.values.channel(channel));
+            }
+            self.values = values;
+        }
+    }
+
+    /// Empties the walls and sets the fixed cells to their values.
+    pub(crate) fn enforce(&self, grid: &mut Grid) {
+        for channel in 0..grid.channels().min(self.values.channels()) {
+            let fixed = self.values.channel(channel);
+            let cells = grid.channel_mut(channel).iter_mut().zip(&self.kinds);
+            for (index, (value, kind)) in cells.enumerate() {
+                match kind {
+                    CellKind::Open => {}
+                    CellKind::Wall => *value = 0.0,
+                    CellKind::Fixed => *value = fixed[index],
+                }
+            }
+        }
+    }
+
+    /// Value the stencil of cell `(x, y)` reads at offset `(dx, dy)`. A wall
+    /// in the way reflects the cell itself, leaving no gradient across it.
+    pub(crate) fn neighbour(
+        &self,
+        grid: &Grid,
+        boundary: Boundary,
+        (x, y): (usize, usize),
+        (dx, dy): (i32, i32),
+        channel: usize,
+    ) -> f32 {
+        if self.blocked(boundary, (x, y), (dx, dy)) {
+            return grid.get(x, y, channel);
+        }
+        boundary.value(grid, x as i32 + dx, y as i32 + dy, channel)
+    }
+
+    /// Whether a wall touches the segment from cell `(x, y)` to the one at
+    /// offset `(dx, dy)`, the cells at both ends included. A segment running
+    /// along the edge between two cells touches both, so the test reads the
+    /// same from either end and no stencil tap reaches past a wall, not even
+    /// diagonally round its corner.
+    pub(crate) fn blocked(
+        &self,
+        boundary: Boundary,
+        (x, y): (usize, usize),
+        (dx, dy): (i32, i32),
+    ) -> bool {
+        let is_wall = |cx: i32, cy: i32| match boundary.neighbour(
+            x as i32 + cx,
+            y as i32 + cy,
+            self.width,
+            self.height,
+        ) {
+            Neighbour::Cell(nx, ny) => self.kinds[ny * self.width + nx] == CellKind::Wall,
+            Neighbour::Value(_) => false,
+        };
+        // Cells holding a coordinate, both of them on the edge in between.
+        let cells = |position: f32| {
+            let rounded = position.round() as i32;
+            if (position - position.floor() - 0.5).abs() < 1e-4 {
+                [position.floor() as i32, position.ceil() as i32]
+            } else {
+                [rounded, rounded]
+            }
+        };
+        // Half cell steps visit every cell the segment touches.
+        let steps = 2 * dx.abs().max(dy.abs());
+        (1..=steps).any(|step| {
+            let t = step as f32 / steps as f32;
+            let [x0, x1] = cells(t * dx as f32);
+            let [y0, y1] = cells(t * dy as f32);
+            is_wall(x0, y0) || is_wall(x1, y0) || is_wall(x0, y1) || is_wall(x1, y1)
+        })
+    }
+}
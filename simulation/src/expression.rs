@@ -0,0 +1,507 @@
+use std::fmt;
+
+// Small arithmetic language for user-typed reaction terms, e.g.
+// `a*u*(1-u) - b*u*v/(1+c*u^2)`. Identifiers are looked up as variables
+// first, then as functions or constants; anything else becomes a named
+// parameter. Function names are reserved and must be called.
+
+#[derive(Clone, Debug)]
+pub struct ParseError {
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "column {}: {}", self.column, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Clone)]
+pub struct Expression {
+    root: Node,
+}
+
+impl Expression {
+    /// Parses `source`, resolving names in `variables` to their index and
+    /// appending every other unknown identifier to `parameters`.
+    pub fn parse(
+        source: &str,
+        variables: &[&str],
+        parameters: &mut Vec<String>,
+    ) -> Result<Self, ParseError> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser {
+            tokens,
+            position: 0,
+            variables,
+            parameters,
+        };
+        let root = parser.expression()?;
+        match parser.peek() {
+            Token::End => Ok(Self { root }),
+            token => Err(parser.error(format!("unexpected {}", token))),
+        }
+    }
+
+    pub fn evaluate(&self, variables: &[f32], parameters: &[f32]) -> f32 {
+        self.root.evaluate(variables, parameters)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum BinaryOperator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Power,
+}
+
+#[derive(Clone, Copy)]
+enum Function {
+    Sin,
+    Cos,
+    Tan,
+    Tanh,
+    Exp,
+    Ln,
+    Sqrt,
+    Abs,
+    Min,
+    Max,
+    Pow,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "sin" => Self::Sin,
+            "cos" => Self::Cos,
+            "tan" => Self::Tan,
+            "tanh" => Self::Tanh,
+            "exp" => Self::Exp,
+            "ln" | "log" => Self::Ln,
+            "sqrt" => Self::Sqrt,
+            "abs" => Self::Abs,
+            "min" => Self::Min,
+            "max" => Self::Max,
+            "pow" => Self::Pow,
+            _ => return None,
+        })
+    }
+
+    fn arity(self) -> usize {
+        match self {
+            Self::Min | Self::Max | Self::Pow => 2,
+            _ => 1,
+        }
+    }
+
+    fn apply(self, arguments: &[f32]) -> f32 {
+        match self {
+            Self::Sin => arguments[0].sin(),
+            Self::Cos => arguments[0].cos(),
+            Self::Tan => arguments[0].tan(),
+            Self::Tanh => arguments[0].tanh(),
+            Self::Exp => arguments[0].exp(),
+            Self::Ln => arguments[0].ln(),
+            Self::Sqrt => arguments[0].sqrt(),
+            Self::Abs => arguments[0].abs(),
+            Self::Min => arguments[0].min(arguments[1]),
+            Self::Max => arguments[0].max(arguments[1]),
+            Self::Pow => arguments[0].powf(arguments[1]),
+        }
+    }
+}
+
+#[derive(Clone)]
+enum Node {
+    Number(f32),
+    Variable(usize),
+    Parameter(usize),
+    Negate(Box<Node>),
+    Binary(BinaryOperator, Box<Node>, Box<Node>),
+    Call(Function, Vec<Node>),
+}
+
+impl Node {
+    fn evaluate(&self, variables: &[f32], parameters: &[f32]) -> f32 {
+        match self {
+            Self::Number(value) => *value,
+            Self::Variable(index) => variables[*index],
+            Self::Parameter(index) => parameters[*index],
+            Self::Negate(operand) => -operand.evaluate(variables, parameters),
+            Self::Binary(operator, left, right) => {
+                let left = left.evaluate(variables, parameters);
+                let right = right.evaluate(variables, parameters);
+                match operator {
+                    BinaryOperator::Add => left + right,
+                    BinaryOperator::Subtract => left - right,
+                    BinaryOperator::Multiply => left * right,
+                    BinaryOperator::Divide => left / right,
+                    BinaryOperator::Power => power(left, right),
+                }
+            }
+            Self::Call(function, arguments) => {
+                let mut values = [0.0; 2];
+                for (value, argument) in values.iter_mut().zip(arguments) {
+                    *value = argument.evaluate(variables, parameters);
+                }
+                function.apply(&values)
+            }
+        }
+    }
+}
+
+fn power(base: f32, exponent: f32) -> f32 {
+    if exponent == 2.0 {
+        base * base
+    } else if exponent.fract() == 0.0 && exponent.abs() < 16.0 {
+        base.powi(exponent as i32)
+    } else {
+        base.powf(exponent)
+    }
+}
+
+#[derive(Clone, PartialEq)]
+enum Token {
+    Number(f32),
+    Identifier(String),
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Caret,
+    LeftParenthesis,
+    RightParenthesis,
+    Comma,
+    End,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Number(value) => write!(f, "number {}", value),
+            Self::Identifier(name) => write!(f, "'{}'", name),
+            Self::Plus => write!(f, "'+'"),
+            Self::Minus => write!(f, "'-'"),
+            Self::Star => write!(f, "'*'"),
+            Self::Slash => write!(f, "'/'"),
+            Self::Caret => write!(f, "'^'"),
+            Self::LeftParenthesis => write!(f, "'('"),
+            Self::RightParenthesis => write!(f, "')'"),
+            Self::Comma => write!(f, "','"),
+            Self::End => write!(f, "end of expression"),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, ParseError> {
+    let characters: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < characters.len() {
+        let c = characters[i];
+        let column = i + 1;
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        if c.is_ascii_digit() || c == '.' {
+            let start = i;
+            while i < characters.len() && (characters[i].is_ascii_digit() || characters[i] == '.') {
+                i += 1;
+            }
+            if i < characters.len() && (characters[i] == 'e' || characters[i] == 'E') {
+                let mut end = i + 1;
+                if end < characters.len() && (characters[end] == '+' || characters[end] == '-') {
+                    end += 1;
+                }
+                if end < characters.len() && characters[end].is_ascii_digit() {
+                    i = end;
+                    while i < characters.len() && characters[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+            }
+            let text: String = characters[start..i].iter().collect();
+            let value = text.parse().map_err(|_| ParseError {
+                column,
+                message: format!("invalid number '{}'", text),
+            })?;
+            tokens.push((Token::Number(value), column));
+            continue;
+        }
+        if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < characters.len() && (characters[i].is_alphanumeric() || characters[i] == '_')
+            {
+                i += 1;
+            }
+            tokens.push((
+                Token::Identifier(characters[start..i].iter().collect()),
+                column,
+            ));
+            continue;
+        }
+        let token = match c {
+            '+' => Token::Plus,
+            '-' => Token::Minus,
+            '*' => Token::Star,
+            '/' => Token::Slash,
+            '^' => Token::Caret,
+            '(' => Token::LeftParenthesis,
+            ')' => Token::RightParenthesis,
+            ',' => Token::Comma,
+            _ => {
+                return Err(ParseError {
+                    column,
+                    message: format!("unexpected character '{}'", c),
+                })
+            }
+        };
+        tokens.push((token, column));
+        i += 1;
+    }
+    tokens.push((Token::End, characters.len() + 1));
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    variables: &'a [&'a str],
+    parameters: &'a mut Vec<String>,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.position].0
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.position].0.clone();
+        if token != Token::End {
+            self.position += 1;
+        }
+        token
+    }
+
+    fn error(&self, message: String) -> ParseError {
+        ParseError {
+            column: self.tokens[self.position].1,
+            message,
+        }
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), ParseError> {
+        if *self.peek() == expected {
+            self.next();
+            Ok(())
+        } else {
+            Err(self.error(format!("expected {} but found {}", expected, self.peek())))
+        }
+    }
+
+    fn expression(&mut self) -> Result<Node, ParseError> {
+        let mut node = self.term()?;
+        loop {
+            let operator = match self.peek() {
+                Token::Plus => BinaryOperator::Add,
+                Token::Minus => BinaryOperator::Subtract,
+                _ => return Ok(node),
+            };
+            self.next();
+            node = Node::Binary(operator, Box::new(node), Box::new(self.term()?));
+        }
+    }
+
+    fn term(&mut self) -> Result<Node, ParseError> {
+        let mut node = self.unary()?;
+        loop {
+            let operator = match self.peek() {
+                Token::Star => BinaryOperator::Multiply,
+                Token::Slash => BinaryOperator::Divide,
+                _ => return Ok(node),
+            };
+            self.next();
+            node = Node::Binary(operator, Box::new(node), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Node, ParseError> {
+        match self.peek() {
+            Token::Minus => {
+                self.next();
+                Ok(Node::Negate(Box::new(self.unary()?)))
+            }
+            Token::Plus => {
+                self.next();
+                self.unary()
+            }
+            _ => self.power(),
+        }
+    }
+
+    fn power(&mut self) -> Result<Node, ParseError> {
+        let base = self.atom()?;
+        if *self.peek() == Token::Caret {
+            self.next();
+            let exponent = self.unary()?;
+            return Ok(Node::Binary(
+                BinaryOperator::Power,
+                Box::new(base),
+                Box::new(exponent),
+            ));
+        }
+        Ok(base)
+    }
+
+    fn atom(&mut self) -> Result<Node, ParseError> {
+        match self.peek().clone() {
+            Token::Number(value) => {
+                self.next();
+                Ok(Node::Number(value))
+            }
+            Token::LeftParenthesis => {
+                self.next();
+                let node = self.expression()?;
+                self.expect(Token::RightParenthesis)?;
+                Ok(node)
+            }
+            Token::Identifier(name) => {
+                let column = self.tokens[self.position].1;
+                self.next();
+                if *self.peek() == Token::LeftParenthesis {
+                    return self.call(&name, column);
+                }
+                self.resolve(name, column)
+            }
+            token => Err(self.error(format!("unexpected {}", token))),
+        }
+    }
+
+    fn call(&mut self, name: &str, column: usize) -> Result<Node, ParseError> {
+        let function = Function::from_name(name).ok_or_else(|| ParseError {
+            column,
+            message: format!("unknown function '{}'", name),
+        })?;
+        self.expect(Token::LeftParenthesis)?;
+        let mut arguments = vec![self.expression()?];
+        while *self.peek() == Token::Comma {
+            self.next();
+            arguments.push(self.expression()?);
+        }
+        self.expect(Token::RightParenthesis)?;
+        if arguments.len() != function.arity() {
+            return Err(ParseError {
+                column,
+                message: format!(
+                    "'{}' takes {} argument(s) but {} were given",
+                    name,
+                    function.arity(),
+                    arguments.len()
+                ),
+            });
+        }
+        Ok(Node::Call(function, arguments))
+    }
+
+    fn resolve(&mut self, name: String, column: usize) -> Result<Node, ParseError> {
+        if let Some(index) = self.variables.iter().position(|variable| *variable == name) {
+            return Ok(Node::Variable(index));
+        }
+        if name == "pi" {
+            return Ok(Node::Number(std::f32::consts::PI));
+        }
+        if Function::from_name(&name).is_some() {
+            return Err(ParseError {
+                column,
+                message: format!("function '{}' needs parentheses, as in {}(u)", name, name),
+            });
+        }
+        let index = match self
+            .parameters
+            .iter()
+            .position(|parameter| *parameter == name)
+        {
+            Some(index) => index,
+            None => {
+                self.parameters.push(name);
+                self.parameters.len() - 1
+            }
+        };
+        Ok(Node::Parameter(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VARIABLES: [&str; 2] = ["u", "v"];
+
+    fn evaluate(source: &str) -> f32 {
+        let mut parameters = Vec::new();
+        let expression = Expression::parse(source, &VARIABLES, &mut parameters).unwrap();
+        let values: Vec<f32> = (1..=parameters.len()).map(|value| value as f32).collect();
+        expression.evaluate(&[0.5, 2.0], &values)
+    }
+
+    fn error(source: &str) -> ParseError {
+        let mut parameters = Vec::new();
+        match Expression::parse(source, &VARIABLES, &mut parameters) {
+            Ok(_) => panic!("'{}' should not parse", source),
+            Err(error) => error,
+        }
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(evaluate("1 + 2 * 3"), 7.0);
+        assert_eq!(evaluate("(1 + 2) * 3"), 9.0);
+        assert_eq!(evaluate("8 / 2 / 2"), 2.0);
+        assert_eq!(evaluate("10 - 4 - 3"), 3.0);
+        assert_eq!(evaluate("2 ^ 3 ^ 2"), 512.0);
+        assert_eq!(evaluate("2 * 3 ^ 2"), 18.0);
+    }
+
+    #[test]
+    fn unary_minus() {
+        assert_eq!(evaluate("-2 ^ 2"), -4.0);
+        assert_eq!(evaluate("2 * -3"), -6.0);
+        assert_eq!(evaluate("--u"), 0.5);
+        assert_eq!(evaluate("2 ^ -1"), 0.5);
+    }
+
+    #[test]
+    fn variables_parameters_and_functions() {
+        let mut parameters = Vec::new();
+        let expression =
+            Expression::parse("a * u - b * v + a", &VARIABLES, &mut parameters).unwrap();
+        assert_eq!(parameters, ["a", "b"]);
+        assert_eq!(expression.evaluate(&[0.5, 2.0], &[3.0, 0.25]), 4.0);
+        assert_eq!(evaluate("max(u, v) + min(u, v)"), 2.5);
+        assert_eq!(evaluate("sqrt(abs(-16)) + exp(0) + ln(1)"), 5.0);
+        assert_eq!(evaluate("pow(v, 3)"), 8.0);
+        assert!((evaluate("sin(pi / 2)") - 1.0).abs() < 1e-6);
+        assert_eq!(evaluate("1.5e1 + .5"), 15.5);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(error("sin + 1").column, 1);
+        assert!(error("u * exp").message.contains("parentheses"));
+        assert_eq!(error("1 +").message, "unexpected end of expression");
+        assert!(error("(1 + 2").message.contains("expected ')'"));
+        assert_eq!(error("foo(1)").message, "unknown function 'foo'");
+        assert!(error("min(1)").message.contains("takes 2 argument(s)"));
+        let character = error("1 $ 2");
+        assert_eq!(character.column, 3);
+        assert_eq!(character.message, "unexpected character '$'");
+        assert_eq!(error("1 2").message, "unexpected number 2");
+    }
+}
@@ -0,0 +1,210 @@
+use std::f32::consts::TAU;
+
+use rustfft::num_complex::Complex;
+
+use super::{
+    convolution::Fft2d,
+    domain::{CellKind, Domain},
+};
+
+/// Incompressible flow on the torus after Jos Stam's "Stable Fluids": the
+/// velocity moves itself semi-Lagrangian, then viscosity and the pressure
+/// projection are solved exactly in Fourier space, so any time step is
+/// stable.
+#[derive(Clone)]
+pub struct Fluid {
+    pub viscosity: f32,
+    width: usize,
+    height: usize,
+    velocity: [Vec<f32>; 2],
+    // Buffers kept between steps.
+    previous: [Vec<f32>; 2],
+    spectra: [Vec<Complex<f32>>; 2],
+    fft: Option<Fft2d>,
+}
+
+impl Default for Fluid {
+    fn default() -> Self {
+        Self {
+            viscosity: 0.1,
+            width: 0,
+            height: 0,
+            velocity: Default::default(),
+            previous: Default::default(),
+            spectra: Default::default(),
+            fft: None,
+        }
+    }
+}
+
+impl Fluid {
+    /// Velocity of cell `(x, y)` in cells per unit of time.
+    pub fn velocity(&self, x: usize, y: usize) -> [f32; 2] {
+        let index = y * self.width + x;
+        match (self.velocity[0].get(index), self.velocity[1].get(index)) {
+            (Some(vx), Some(vy)) => [*vx, *vy],
+            _ => [0.0, 0.0],
+        }
+    }
+
+    pub fn max_speed(&self) -> f32 {
+        self.velocity[0]
+            .iter()
+            .zip(&self.velocity[1])
+            .fold(0.0, |max, (vx, vy)| max.max(vx.abs() + vy.abs()))
+    }
+
+    /// Brings the fluid to rest.
+    pub fn clear(&mut self) {
+        for component in &mut self.velocity {
+            component.fill(0.0);
+        }
+    }
+
+    /// Size of the velocity field, zero before the first step.
+    pub(crate) fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    pub(crate) fn velocity_components(&self) -> [&[f32]; 2] {
+        [&self.velocity[0], &self.velocity[1]]
+    }
+
+    /// Puts back the velocity components of a `width` x `height` field
+    /// recorded earlier.
+    pub(crate) fn set_velocity(&mut self, width: usize, height: usize, components: [&[f32]; 2]) {
+        self.fit(width, height);
+        for (component, values) in self.velocity.iter_mut().zip(components) {
+            component.copy_from_slice(values);
+        }
+    }
+
+    /// Brings the fluid to rest at a new resolution if it changed.
+    pub(crate) fn fit(&mut self, width: usize, height: usize) {
+        if (self.width, self.height) != (width, height) {
+            self.width = width;
+            self.height = height;
+            for component in self.velocity.iter_mut().chain(self.previous.iter_mut()) {
+                *component = vec![0.0; width * height];
+            }
+            for spectrum in &mut self.spectra {
+                *spectrum = vec![Complex::default(); width * height];
+            }
+            self.fft = Some(Fft2d::new(width, height));
+        }
+    }
+
+    /// Adds `impulse` to the velocity of the cells within `radius` of
+    /// `(center_x, center_y)`, fading out towards the rim.
+    pub(crate) fn add_momentum(
+        &mut self,
+        center_x: i32,
+        center_y: i32,
+        radius: usize,
+        impulse: [f32; 2],
+    ) {
+        let radius = radius.max(1) as i32;
+        for dy in -radius..=radius {
+            for dx in -radius..=radius {
+                let distance = ((dx * dx + dy * dy) as f32).sqrt() / radius as f32;
+                if distance > 1.0 {
+                    continue;
+                }
+                let index = self.index(center_x + dx, center_y + dy);
+                for (component, impulse) in self.velocity.iter_mut().zip(impulse) {
+                    component[index] += (1.0 - distance) * impulse;
+                }
+            }
+        }
+    }
+
+    pub(crate) fn step(&mut self, dt: f32) {
+        if self.fft.is_none() {
+            return;
+        }
+        for component in 0..2 {
+            self.previous[component].copy_from_slice(&self.velocity[component]);
+        }
+        self.advect_velocity(dt);
+        let (width, height) = (self.width, self.height);
+        let Some(fft) = &mut self.fft else {
+            return;
+        };
+        for (spectrum, component) in self.spectra.iter_mut().zip(&self.velocity) {
+            for (value, velocity) in spectrum.iter_mut().zip(component) {
+                *value = Complex::new(*velocity, 0.0);
+            }
+            fft.process(spectrum, true);
+        }
+        let [spectrum_x, spectrum_y] = &mut self.spectra;
+        for (index, (vx, vy)) in spectrum_x.iter_mut().zip(spectrum_y.iter_mut()).enumerate() {
+            let kx = TAU * (index % width) as f32 / width as f32;
+            let ky = TAU * (index / width) as f32 / height as f32;
+            // Removing the component along the central difference gradient
+            // leaves no divergence as the channels' stencils measure it.
+            let (gx, gy) = (kx.sin(), ky.sin());
+            let norm = gx * gx + gy * gy;
+            if norm > 1e-6 {
+                let along = (*vx * gx + *vy * gy) / norm;
+                *vx -= along * gx;
+                *vy -= along * gy;
+            }
+            // Implicit viscosity with the eigenvalue of the five point
+            // Laplacian.
+            let laplacian = 4.0 * ((0.5 * kx).sin().powi(2) + (0.5 * ky).sin().powi(2));
+            let damping = 1.0 / (1.0 + self.viscosity * dt * laplacian);
+            *vx *= damping;
+            *vy *= damping;
+        }
+        let scale = 1.0 / (width * height) as f32;
+        for (spectrum, component) in self.spectra.iter_mut().zip(&mut self.velocity) {
+            fft.process(spectrum, false);
+            for (velocity, value) in component.iter_mut().zip(spectrum.iter()) {
+                *velocity = value.re * scale;
+            }
+        }
+    }
+
+    /// Brings the walls of `domain` to rest, so the fluid sticks to them.
+    pub(crate) fn stop_at_walls(&mut self, domain: &Domain) {
+        let width = self.width;
+        let [velocity_x, velocity_y] = &mut self.velocity;
+        let cells = velocity_x.iter_mut().zip(velocity_y.iter_mut());
+        for (index, (vx, vy)) in cells.enumerate() {
+            if domain.kind(index % width, index / width) == CellKind::Wall {
+                (*vx, *vy) = (0.0, 0.0);
+            }
+        }
+    }
+
+    // Periodic index of a cell.
+    fn index(&self, x: i32, y: i32) -> usize {
+        let x = x.rem_euclid(self.width as i32) as usize;
+        let y = y.rem_euclid(self.height as i32) as usize;
+        y * self.width + x
+    }
+
+    // Moves the velocity along itself, tracing every cell back through the
+    // previous velocity.
+    fn advect_velocity(&mut self, dt: f32) {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let index = y * self.width + x;
+                let (px, py) = (
+                    x as f32 - dt * self.previous[0][index],
+                    y as f32 - dt * self.previous[1][index],
+                );
+                let (x0, y0) = (px.floor(), py.floor());
+                let (fx, fy) = (px - x0, py - y0);
+                let (x0, y0) = (x0 as i32, y0 as i32);
+                for component in 0..2 {
+                    let values = &self.previous[component];
+                    let value = |x, y| values[self.index(x, y)];
+                    let top = value(x0, y0) * (1.0 - fx) + value(x0 + 1, y0) * fx;
+                    let bottom = value(x0, y0 + 1) * (1.0 - fx) + value(x0 + 1, y0 + 1) * fx;
+                    self.velocity[component][index] = top * (1.0 - fy) + bottom * fy;
+                }
+            }
+        }
+    }
+}
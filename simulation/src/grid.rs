@@ -0,0 +1,213 @@
+use rand::{distributions::Distribution, Rng};
+
+use super::boundary::Boundary;
+
+/// Horizontal band of rows of a grid, with a mutable slice of every channel.
+pub struct Band<'a> {
+    pub first_row: usize,
+    pub rows: usize,
+    pub channels: Vec<&'a mut [f32]>,
+}
+
+#[derive(Clone, Default)]
+pub struct Grid {
+    width: usize,
+    height: usize,
+    channels: usize,
+    values: Vec<f32>,
+}
+
+impl Grid {
+    pub fn new(width: usize, height: usize, channels: usize) -> Self {
+        Self {
+            width,
+            height,
+            channels,
+            values: vec![0.0; channels * width * height],
+        }
+    }
+
+    pub fn random(width: usize, height: usize, channels: usize, rng: &mut impl Rng) -> Self {
+        let mut grid = Self::new(width, height, channels);
+        for channel in 0..channels {
+            grid.randomize_channel(channel, rng);
+        }
+        grid
+    }
+
+    fn randomize_channel(&mut self, channel: usize, rng: &mut impl Rng) {
+        let p = rand::distributions::Uniform::new_inclusive(0.0, 1.0);
+        let (width, height) = (self.width as f32, self.height as f32);
+        for x in 0..self.width {
+            for y in 0..self.height {
+                let weight = match channel % 3 {
+                    0 => y as f32 / height,
+                    1 => (x as f32) * (y as f32) / (height * width),
+                    _ => x as f32 / width,
+                };
+                self.set(x, y, channel, p.sample(rng) * weight);
+            }
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    pub fn channel(&self, channel: usize) -> &[f32] {
+        let len = self.width * self.height;
+        &self.values[channel * len..(channel + 1) * len]
+    }
+
+    pub fn channel_mut(&mut self, channel: usize) -> &mut [f32] {
+        let len = self.width * self.height;
+        &mut self.values[channel * len..(channel + 1) * len]
+    }
+
+    pub(crate) fn same_shape(&self, other: &Grid) -> bool {
+        (self.width, self.height, self.channels) == (other.width, other.height, other.channels)
+    }
+
+    /// `self += scale * other`, cell by cell.
+    pub(crate) fn add_scaled(&mut self, other: &Grid, scale: f32) {
+        for (value, other) in self.values.iter_mut().zip(&other.values) {
+            *value += scale * other;
+        }
+    }
+
+    /// `self = base + scale * slope`, cell by cell.
+    pub(crate) fn set_sum(&mut self, base: &Grid, slope: &Grid, scale: f32) {
+        for ((value, base), slope) in self.values.iter_mut().zip(&base.values).zip(&slope.values) {
+            *value = base + scale * slope;
+        }
+    }
+
+    pub(crate) fn max_difference(&self, other: &Grid) -> f32 {
+        self.values
+            .iter()
+            .zip(&other.values)
+            .map(|(a, b)| (a - b).abs())
+            // NaN wins, so a blown up stage is never mistaken for a small error.
+            .fold(0.0, |max, difference| {
+                if difference > max || difference.is_nan() {
+                    difference
+                } else {
+                    max
+                }
+            })
+    }
+
+    pub(crate) fn clamp_non_negative(&mut self) {
+        for value in self.values.iter_mut() {
+            *value = value.max(0.0);
+        }
+    }
+
+    /// Splits the grid into bands of at most `rows` rows.
+    pub(crate) fn bands_mut(&mut self, rows: usize) -> impl Iterator<Item = Band<'_>> {
+        let (width, height) = (self.width, self.height);
+        let mut planes: Vec<_> = self
+            .values
+            .chunks_mut(width * height)
+            .map(|plane| plane.chunks_mut(rows * width))
+            .collect();
+        (0..height).step_by(rows).map(move |first_row| Band {
+            first_row,
+            rows: rows.min(height - first_row),
+            channels: planes
+                .iter_mut()
+                .map(|plane| plane.next().unwrap())
+                .collect(),
+        })
+    }
+
+    pub fn get(&self, x: usize, y: usize, channel: usize) -> f32 {
+        self.values[(channel * self.height + y) * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, channel: usize, value: f32) {
+        self.values[(channel * self.height + y) * self.width + x] = value;
+    }
+
+    pub fn set_channel_count(&mut self, channels: usize, rng: &mut impl Rng) {
+        let old_channels = self.channels;
+        self.values.resize(channels * self.width * self.height, 0.0);
+        self.channels = channels;
+        for channel in old_channels..channels {
+            self.randomize_channel(channel, rng);
+        }
+    }
+
+    /// Bilinear resampling of every channel to a new resolution.
+    pub fn resampled(&self, width: usize, height: usize) -> Self {
+        let mut grid = Self::new(width, height, self.channels);
+        for y in 0..height {
+            for x in 0..width {
+                for channel in 0..self.channels {
+                    grid.set(x, y, channel, self.sample(x, y, width, height, channel));
+                }
+            }
+        }
+        grid
+    }
+
+    /// Bilinear value of `channel` at cell `(x, y)` of a `width` x `height`
+    /// grid stretched over this one, cell centres aligned and clamped at the
+    /// edges.
+    pub(crate) fn sample(
+        &self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        channel: usize,
+    ) -> f32 {
+        let axis = |position: usize, size: usize, old_size: usize| {
+            let source = ((position as f32 + 0.5) * old_size as f32 / size as f32 - 0.5)
+                .clamp(0.0, (old_size - 1) as f32);
+            let low = source.floor() as usize;
+            (low, (low + 1).min(old_size - 1), source - low as f32)
+        };
+        let (x0, x1, fx) = axis(x, width, self.width);
+        let (y0, y1, fy) = axis(y, height, self.height);
+        let top = self.get(x0, y0, channel) * (1.0 - fx) + self.get(x1, y0, channel) * fx;
+        let bottom = self.get(x0, y1, channel) * (1.0 - fx) + self.get(x1, y1, channel) * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+
+    pub fn paint(
+        &mut self,
+        center_x: i32,
+        center_y: i32,
+        radius: usize,
+        values: &[f32],
+        boundary: Boundary,
+    ) {
+        let radius = radius as i32;
+        for r in 0..2 * radius {
+            for s in 0..2 * radius {
+                if (r - radius) * (r - radius) + (s - radius) * (s - radius) <= radius * radius {
+                    let Some((x, y)) = boundary.wrap(
+                        center_x + r - radius,
+                        center_y + s - radius,
+                        self.width,
+                        self.height,
+                    ) else {
+                        continue;
+                    };
+                    for (channel, value) in values.iter().enumerate().take(self.channels) {
+                        self.set(x, y, channel, *value);
+                    }
+                }
+            }
+        }
+    }
+}
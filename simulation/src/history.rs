@@ -0,0 +1,394 @@
+use std::collections::VecDeque;
+
+use rand_chacha::ChaCha8Rng;
+
+use super::{advection::Flow, fluid::Fluid, grid::Grid, state::Simulation};
+
+// Every this many frames one is stored on its own, the others only hold the
+// change since the frame before, which bounds the work to decode any frame.
+const KEYFRAME_INTERVAL: usize = 16;
+
+/// Ring buffer of past fields, keyed by iteration.
+///
+/// A frame holds the field, the random state and the velocity of a fluid
+/// flow, everything a resumed run depends on. Frames are stored as the XOR of their bits with the previous frame, split
+/// into byte planes and run-length encoded, so slowly changing and discrete
+/// fields take little memory. When full, the oldest group of frames up to the
+/// next keyframe is dropped.
+#[derive(Clone)]
+pub struct History {
+    /// Maximum number of frames kept, 0 disables recording.
+    pub capacity: usize,
+    /// Only iterations that are a multiple of this are recorded.
+    pub stride: u64,
+    frames: VecDeque<Frame>,
+    // Bits of the newest frame, the reference of the next delta.
+    last: Vec<u32>,
+    // Buffers kept between frames.
+    bits: Vec<u32>,
+    plane: Vec<u8>,
+}
+
+#[derive(Clone)]
+struct Frame {
+    iteration: u64,
+    shape: (usize, usize, usize),
+    // Size of the fluid velocity field following the field, zero without
+    // a fluid flow.
+    velocity: (usize, usize),
+    rng: ChaCha8Rng,
+    keyframe: bool,
+    data: Vec<u8>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            capacity: 256,
+            stride: 2,
+            frames: VecDeque::new(),
+            last: Vec::new(),
+            bits: Vec::new(),
+            plane: Vec::new(),
+        }
+    }
+}
+
+impl History {
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn iteration(&self, index: usize) -> u64 {
+        self.frames[index].iteration
+    }
+
+    /// Index of the newest frame recorded at or before `iteration`.
+    pub fn position(&self, iteration: u64) -> Option<usize> {
+        self.frames
+            .iter()
+            .rposition(|frame| frame.iteration <= iteration)
+    }
+
+    /// Memory taken by the encoded frames.
+    pub fn bytes(&self) -> usize {
+        self.frames.iter().map(|frame| frame.data.len()).sum()
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.last.clear();
+    }
+
+    /// Drops every frame after `index`.
+    pub fn truncate(&mut self, index: usize) {
+        if index + 1 < self.frames.len() {
+            self.frames.truncate(index + 1);
+            self.last = self.decode(index);
+        }
+    }
+
+    /// Stores the field and random state of `simulation` as `iteration`,
+    /// replacing any frames from that iteration on.
+    pub fn record(&mut self, iteration: u64, simulation: &Simulation) {
+        if self.capacity == 0 || iteration % self.stride.max(1) != 0 {
+            return;
+        }
+        if let Some(index) = self.position(iteration.saturating_sub(1)) {
+            self.truncate(index);
+        }
+        if self
+            .frames
+            .back()
+            .is_some_and(|frame| frame.iteration >= iteration)
+        {
+            self.clear();
+        }
+        let grid = &simulation.grid;
+        let shape = (grid.width(), grid.height(), grid.channels());
+        let fluid = match &simulation.advection.flow {
+            Flow::Fluid(fluid) => Some(&**fluid),
+            _ => None,
+        };
+        let velocity = fluid.map_or((0, 0), Fluid::size);
+        let since_keyframe = self.frames.iter().rev().position(|frame| frame.keyframe);
+        // Small buffers need shorter groups to keep frames after an eviction.
+        let interval = KEYFRAME_INTERVAL.min(self.capacity / 2).max(1);
+        let keyframe = match (self.frames.back(), since_keyframe) {
+            (Some(last), Some(since)) => {
+                (last.shape, last.velocity) != (shape, velocity) || since + 1 >= interval
+            }
+            _ => true,
+        };
+        self.bits.clear();
+        self.bits.extend(
+            (0..grid.channels())
+                .flat_map(|channel| grid.channel(channel).iter().map(|value| value.to_bits())),
+        );
+        for component in fluid.iter().flat_map(|fluid| fluid.velocity_components()) {
+            self.bits
+                .extend(component.iter().map(|value| value.to_bits()));
+        }
+        let mut data = Vec::new();
+        for byte in 0..4 {
+            self.plane.clear();
+            for (index, value) in self.bits.iter().enumerate() {
+                let delta = if keyframe {
+                    *value
+                } else {
+                    value ^ self.last[index]
+                };
+                self.plane.push((delta >> (8 * byte)) as u8);
+            }
+            pack_bits(&self.plane, &mut data);
+        }
+        self.frames.push_back(Frame {
+            iteration,
+            shape,
+            velocity,
+            rng: simulation.rng().clone(),
+            keyframe,
+            data,
+        });
+        std::mem::swap(&mut self.last, &mut self.bits);
+        while self.frames.len() > self.capacity {
+            self.frames.pop_front();
+            while self.frames.front().is_some_and(|frame| !frame.keyframe) {
+                self.frames.pop_front();
+            }
+        }
+        if self.frames.is_empty() {
+            self.last.clear();
+        }
+    }
+
+    /// Puts the field, random state and fluid velocity of frame `index` back
+    /// into `simulation` and returns its iteration. A fluid flow comes to
+    /// rest if the frame was recorded without one. Fails if the frame has another
+    /// channel count than the simulation.
+    pub fn restore(&self, index: usize, simulation: &mut Simulation) -> Option<u64> {
+        let frame = self.frames.get(index)?;
+        let (width, height, channels) = frame.shape;
+        if channels != simulation.channel_count() {
+            return None;
+        }
+        let bits = self.decode(index);
+        let (field, velocity) = bits.split_at(width * height * channels);
+        let mut grid = Grid::new(width, height, channels);
+        for (channel, bits) in field.chunks(width * height).enumerate() {
+            for (value, bits) in grid.channel_mut(channel).iter_mut().zip(bits) {
+                *value = f32::from_bits(*bits);
+            }
+        }
+        simulation.set_grid(grid);
+        if let Flow::Fluid(fluid) = &mut simulation.advection.flow {
+            let velocity: Vec<f32> = velocity.iter().map(|bits| f32::from_bits(*bits)).collect();
+            match frame.velocity {
+                (0, _) | (_, 0) => fluid.clear(),
+                (width, height) => {
+                    let (vx, vy) = velocity.split_at(width * height);
+                    fluid.set_velocity(width, height, [vx, vy]);
+                }
+            }
+        }
+        simulation.set_rng(frame.rng.clone());
+        Some(frame.iteration)
+    }
+
+    fn decode(&self, index: usize) -> Vec<u32> {
+        let first = (0..=index)
+            .rev()
+            .find(|index| self.frames[*index].keyframe)
+            .unwrap_or(0);
+        let frame = &self.frames[index];
+        let (width, height, channels) = frame.shape;
+        let (velocity_width, velocity_height) = frame.velocity;
+        let mut bits = vec![0u32; width * height * channels + 2 * velocity_width * velocity_height];
+        let mut plane = Vec::with_capacity(bits.len());
+        for frame in self.frames.range(first..=index) {
+            let mut data = frame.data.as_slice();
+            for byte in 0..4 {
+                plane.clear();
+                data = unpack_bits(data, bits.len(), &mut plane);
+                for (value, delta) in bits.iter_mut().zip(&plane) {
+                    *value ^= (*delta as u32) << (8 * byte);
+                }
+            }
+        }
+        bits
+    }
+}
+
+// PackBits: a header below 128 is followed by that many plus one literal
+// bytes, a header of 128 or more by one byte repeated `header - 125` times.
+fn pack_bits(bytes: &[u8], out: &mut Vec<u8>) {
+    let run_at = |start: usize| {
+        bytes[start..]
+            .iter()
+            .take(130)
+            .take_while(|byte| **byte == bytes[start])
+            .count()
+    };
+    let mut index = 0;
+    while index < bytes.len() {
+        let run = run_at(index);
+        if run >= 3 {
+            out.push((run + 125) as u8);
+            out.push(bytes[index]);
+            index += run;
+            continue;
+        }
+        let start = index;
+        while index < bytes.len() && index - start < 128 && run_at(index) < 3 {
+            index += 1;
+        }
+        out.push((index - start - 1) as u8);
+        out.extend_from_slice(&bytes[start..index]);
+    }
+}
+
+// Decodes `count` bytes into `out`, returning the rest of `data`.
+fn unpack_bits<'a>(mut data: &'a [u8], count: usize, out: &mut Vec<u8>) -> &'a [u8] {
+    let end = out.len() + count;
+    while out.len() < end {
+        let header = data[0] as usize;
+        if header < 128 {
+            out.extend_from_slice(&data[1..header + 2]);
+            data = &data[header + 2..];
+        } else {
+            out.extend(std::iter::repeat(data[1]).take(header - 125));
+            data = &data[2..];
+        }
+    }
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::seeder::Seeder;
+
+    // Bits of the field and of the fluid velocity, if any.
+    fn bits(simulation: &Simulation) -> Vec<u32> {
+        let grid = &simulation.grid;
+        let mut bits: Vec<u32> = (0..grid.channels())
+            .flat_map(|channel| grid.channel(channel).iter().map(|value| value.to_bits()))
+            .collect();
+        if let Flow::Fluid(fluid) = &simulation.advection.flow {
+            for component in fluid.velocity_components() {
+                bits.extend(component.iter().map(|value| value.to_bits()));
+            }
+        }
+        bits
+    }
+
+    // Records `frames` steps of a simulation seeded by `seeder`, returning
+    // the bits and random state of every frame.
+    fn record(
+        history: &mut History,
+        simulation: &mut Simulation,
+        seeder: Seeder,
+        frames: u64,
+    ) -> Vec<(Vec<u32>, ChaCha8Rng)> {
+        simulation.seeder = seeder;
+        simulation.reset();
+        simulation.add_momentum(8, 8, 4, [2.0, 1.0]);
+        let mut recorded = Vec::new();
+        for iteration in 0..frames {
+            if iteration == frames / 2 {
+                simulation.resize(20, 12);
+                simulation.add_momentum(8, 8, 4, [2.0, 1.0]);
+            }
+            history.record(iteration, simulation);
+            recorded.push((bits(simulation), simulation.rng().clone()));
+            simulation.step();
+        }
+        recorded
+    }
+
+    #[test]
+    fn restores_every_frame() {
+        for seeder in [
+            Seeder::WhiteNoise {
+                mean: 0.5,
+                amplitude: 0.5,
+            },
+            Seeder::Uniform { value: 0.25 },
+        ] {
+            let mut history = History {
+                stride: 1,
+                ..History::default()
+            };
+            let mut simulation = Simulation::default();
+            simulation.resize(24, 16);
+            let recorded = record(&mut history, &mut simulation, seeder, 40);
+            assert_eq!(history.len(), recorded.len());
+            // Scrub backwards, the way the timeline slider does.
+            for (iteration, (bits_then, rng_then)) in recorded.iter().enumerate().rev() {
+                let index = history.position(iteration as u64).unwrap();
+                assert_eq!(
+                    history.restore(index, &mut simulation),
+                    Some(iteration as u64)
+                );
+                assert!(bits(&simulation) == *bits_then, "{}", seeder.name());
+                assert!(simulation.rng() == rng_then);
+            }
+        }
+    }
+
+    #[test]
+    fn recording_after_a_scrub_drops_the_later_frames() {
+        let mut history = History {
+            stride: 1,
+            ..History::default()
+        };
+        let mut simulation = Simulation::default();
+        simulation.resize(24, 16);
+        // The branch resumes from the past velocity too.
+        simulation.advection.flow = Flow::Fluid(Box::default());
+        let seeder = Seeder::Uniform { value: 0.5 };
+        let recorded = record(&mut history, &mut simulation, seeder, 30);
+        assert!(
+            matches!(&simulation.advection.flow, Flow::Fluid(fluid) if fluid.max_speed() > 0.0)
+        );
+        history.restore(10, &mut simulation).unwrap();
+        assert!(bits(&simulation) == recorded[10].0);
+        simulation.step();
+        history.record(11, &simulation);
+        assert_eq!(history.len(), 12);
+        assert_eq!(history.iteration(11), 11);
+        history.restore(10, &mut simulation).unwrap();
+        assert!(bits(&simulation) == recorded[10].0);
+    }
+
+    #[test]
+    fn evicts_the_oldest_frames_at_capacity() {
+        let mut history = History {
+            capacity: 8,
+            stride: 1,
+            ..History::default()
+        };
+        let mut simulation = Simulation::default();
+        simulation.resize(24, 16);
+        let seeder = Seeder::WhiteNoise {
+            mean: 0.5,
+            amplitude: 0.5,
+        };
+        let recorded = record(&mut history, &mut simulation, seeder, 40);
+        assert!(history.len() <= 8 && history.len() >= 4);
+        assert_eq!(history.iteration(history.len() - 1), 39);
+        // The oldest frame left still decodes on its own.
+        assert!(history.frames[0].keyframe);
+        for index in 0..history.len() {
+            let iteration = history.restore(index, &mut simulation).unwrap();
+            assert!(bits(&simulation) == recorded[iteration as usize].0);
+        }
+        history.capacity = 0;
+        history.record(40, &simulation);
+        assert_eq!(history.iteration(history.len() - 1), 39);
+    }
+}
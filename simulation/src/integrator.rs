@@ -0,0 +1,152 @@
+use super::grid::Grid;
+
+// Limits on how much the adaptive step may shrink or grow after one try.
+const MIN_STEP_FACTOR: f32 = 0.2;
+const MAX_STEP_FACTOR: f32 = 5.0;
+// Smallest fraction of dt the adaptive integrator may step by.
+const MIN_STEP_FRACTION: f32 = 1e-4;
+
+/// Time integration scheme of the reaction-diffusion update.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Integrator {
+    Euler,
+    Heun,
+    RungeKutta4,
+    /// Heun's method with an embedded Euler step as error estimate,
+    /// sub-stepping so the largest local error per step stays below
+    /// `tolerance`.
+    Adaptive {
+        tolerance: f32,
+    },
+}
+
+impl Integrator {
+    pub const PRESETS: [Integrator; 4] = [
+        Integrator::Euler,
+        Integrator::Heun,
+        Integrator::RungeKutta4,
+        Integrator::Adaptive { tolerance: 1e-3 },
+    ];
+
+    /// Extent of the stability region along the negative real axis: a
+    /// decay rate `r` is integrated stably while `r * dt` stays below it.
+    pub fn stability_radius(&self) -> f32 {
+        match self {
+            Self::Euler | Self::Heun | Self::Adaptive { .. } => 2.0,
+            Self::RungeKutta4 => 2.785,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Euler => "Euler",
+            Self::Heun => "Heun",
+            Self::RungeKutta4 => "Runge-Kutta 4",
+            Self::Adaptive { .. } => "Adaptive Heun-Euler",
+        }
+    }
+}
+
+impl Default for Integrator {
+    fn default() -> Self {
+        Self::Euler
+    }
+}
+
+/// Buffers for the intermediate stages, kept between steps.
+#[derive(Clone, Default)]
+pub struct Workspace {
+    stage: Grid,
+    slopes: [Grid; 4],
+    // Last accepted step of the adaptive integrator.
+    adaptive_step: Option<f32>,
+}
+
+impl Workspace {
+    fn fit(&mut self, grid: &Grid) {
+        for buffer in std::iter::once(&mut self.stage).chain(self.slopes.iter_mut()) {
+            if !buffer.same_shape(grid) {
+                *buffer = Grid::new(grid.width(), grid.height(), grid.channels());
+            }
+        }
+    }
+}
+
+/// Advances `state` by `dt` with the time derivative given by `derivative`,
+/// which writes the derivative of its first argument into the second.
+/// Returns the number of steps taken.
+pub fn integrate(
+    integrator: Integrator,
+    state: &mut Grid,
+    dt: f32,
+    workspace: &mut Workspace,
+    mut derivative: impl FnMut(&Grid, &mut Grid),
+) -> usize {
+    workspace.fit(state);
+    let Workspace {
+        stage,
+        slopes: [k1, k2, k3, k4],
+        adaptive_step,
+    } = workspace;
+    match integrator {
+        Integrator::Euler => {
+            derivative(state, k1);
+            state.add_scaled(k1, dt);
+            1
+        }
+        Integrator::Heun => {
+            derivative(state, k1);
+            stage.set_sum(state, k1, dt);
+            derivative(stage, k2);
+            state.add_scaled(k1, 0.5 * dt);
+            state.add_scaled(k2, 0.5 * dt);
+            1
+        }
+        Integrator::RungeKutta4 => {
+            derivative(state, k1);
+            stage.set_sum(state, k1, 0.5 * dt);
+            derivative(stage, k2);
+            stage.set_sum(state, k2, 0.5 * dt);
+            derivative(stage, k3);
+            stage.set_sum(state, k3, dt);
+            derivative(stage, k4);
+            state.add_scaled(k1, dt / 6.0);
+            state.add_scaled(k2, dt / 3.0);
+            state.add_scaled(k3, dt / 3.0);
+            state.add_scaled(k4, dt / 6.0);
+            1
+        }
+        Integrator::Adaptive { tolerance } => {
+            let mut remaining = dt;
+            let mut step = adaptive_step.unwrap_or(dt).min(dt);
+            let mut steps = 0;
+            while remaining > 0.0 {
+                step = step.min(remaining);
+                derivative(state, k1);
+                stage.set_sum(state, k1, step);
+                derivative(stage, k2);
+                // Heun and Euler differ by step / 2 * (k2 - k1).
+                let error = 0.5 * step * k1.max_difference(k2);
+                let accepted = error <= tolerance || step <= MIN_STEP_FRACTION * dt;
+                if accepted {
+                    state.add_scaled(k1, 0.5 * step);
+                    state.add_scaled(k2, 0.5 * step);
+                    remaining -= step;
+                    steps += 1;
+                }
+                let factor = if error > 0.0 && error.is_finite() {
+                    (0.9 * (tolerance / error).sqrt()).clamp(MIN_STEP_FACTOR, MAX_STEP_FACTOR)
+                } else if error == 0.0 {
+                    MAX_STEP_FACTOR
+                } else {
+                    MIN_STEP_FACTOR
+                };
+                step = (step * factor).clamp(MIN_STEP_FRACTION * dt, dt);
+                if accepted {
+                    *adaptive_step = Some(step);
+                }
+            }
+            steps
+        }
+    }
+}
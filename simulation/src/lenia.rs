@@ -0,0 +1,195 @@
+use super::{boundary::Boundary, convolution::FftConvolution, grid::Grid, parallel};
+
+// Kernels with more taps than this are convolved through the FFT.
+const DIRECT_TAPS_LIMIT: usize = 120;
+
+/// Continuous cellular automaton after Bert Chan's Lenia: every channel is
+/// convolved with a smooth ring kernel, mapped through a Gaussian growth
+/// function and integrated with a time step.
+#[derive(Clone)]
+pub struct Lenia {
+    pub radius: f32,
+    pub peaks: Vec<f32>,
+    pub growth_center: f32,
+    pub growth_width: f32,
+    pub time_step: f32,
+    kernel: Option<Box<LeniaKernel>>,
+}
+
+#[derive(Clone)]
+struct LeniaKernel {
+    radius: f32,
+    peaks: Vec<f32>,
+    taps: Vec<(i32, i32, f32)>,
+    fft: Option<FftConvolution>,
+    // Buffers kept between steps.
+    potential: Grid,
+    padded: Vec<f32>,
+    padded_potential: Vec<f32>,
+}
+
+impl Default for Lenia {
+    fn default() -> Self {
+        Self {
+            radius: 13.0,
+            peaks: vec![1.0],
+            growth_center: 0.15,
+            growth_width: 0.015,
+            time_step: 0.1,
+            kernel: None,
+        }
+    }
+}
+
+impl Lenia {
+    pub fn step(&mut self, grid: &mut Grid, boundary: Boundary) {
+        let (width, height) = (grid.width(), grid.height());
+        let (center, growth_width, time_step) =
+            (self.growth_center, self.growth_width, self.time_step);
+        // The FFT convolution is periodic by construction, other boundaries
+        // convolve a copy padded with the cells beyond the edges.
+        let padding = if boundary.is_periodic() {
+            0
+        } else {
+            self.radius.ceil() as usize
+        };
+        let (padded_width, padded_height) = (width + 2 * padding, height + 2 * padding);
+        let channels = grid.channels();
+        let LeniaKernel {
+            taps,
+            fft,
+            potential,
+            padded,
+            padded_potential,
+            ..
+        } = self.kernel(padded_width, padded_height);
+        if (potential.width(), potential.height(), potential.channels())
+            != (width, height, channels)
+        {
+            *potential = Grid::new(width, height, channels);
+        }
+        match fft {
+            Some(fft) => {
+                for channel in 0..channels {
+                    let output = potential.channel_mut(channel);
+                    if padding == 0 {
+                        fft.apply(grid.channel(channel), output);
+                        continue;
+                    }
+                    padded.clear();
+                    for y in 0..padded_height as i32 {
+                        for x in 0..padded_width as i32 {
+                            let offset = padding as i32;
+                            padded.push(boundary.value(grid, x - offset, y - offset, channel));
+                        }
+                    }
+                    padded_potential.resize(padded.len(), 0.0);
+                    fft.apply(padded, padded_potential);
+                    for y in 0..height {
+                        let row = (y + padding) * padded_width + padding;
+                        output[y * width..(y + 1) * width]
+                            .copy_from_slice(&padded_potential[row..row + width]);
+                    }
+                }
+            }
+            None => {
+                let (source, taps) = (&*grid, &*taps);
+                let bands = potential.bands_mut(parallel::band_rows(height));
+                parallel::for_each_band(bands, |mut band| {
+                    for row in 0..band.rows {
+                        let y = (band.first_row + row) as i32;
+                        for x in 0..width {
+                            for (channel, output) in band.channels.iter_mut().enumerate() {
+                                output[row * width + x] =
+                                    boundary.sum_neighbours(source, x as i32, y, channel, taps);
+                            }
+                        }
+                    }
+                });
+            }
+        }
+        let potential = &*potential;
+        let bands = grid.bands_mut(parallel::band_rows(height));
+        parallel::for_each_band(bands, |mut band| {
+            for (channel, values) in band.channels.iter_mut().enumerate() {
+                let offset = band.first_row * width;
+                let potential = &potential.channel(channel)[offset..offset + values.len()];
+                for (value, potential) in values.iter_mut().zip(potential) {
+                    let growth = growth(*potential, center, growth_width);
+                    *value = (*value + time_step * growth).clamp(0.0, 1.0);
+                }
+            }
+        });
+    }
+
+    fn kernel(&mut self, width: usize, height: usize) -> &mut LeniaKernel {
+        let outdated = match &self.kernel {
+            Some(kernel) => {
+                kernel.radius != self.radius
+                    || kernel.peaks != self.peaks
+                    || kernel
+                        .fft
+                        .as_ref()
+                        .is_some_and(|fft| fft.size() != (width, height))
+            }
+            None => true,
+        };
+        if outdated {
+            let taps = ring_kernel(self.radius, &self.peaks);
+            let fft =
+                (taps.len() > DIRECT_TAPS_LIMIT).then(|| FftConvolution::new(width, height, &taps));
+            self.kernel = Some(Box::new(LeniaKernel {
+                radius: self.radius,
+                peaks: self.peaks.clone(),
+                taps,
+                fft,
+                potential: Grid::new(0, 0, 0),
+                padded: Vec::new(),
+                padded_potential: Vec::new(),
+            }));
+        }
+        self.kernel.as_mut().unwrap()
+    }
+}
+
+fn growth(potential: f32, center: f32, width: f32) -> f32 {
+    let distance = (potential - center) / width;
+    2.0 * (-0.5 * distance * distance).exp() - 1.0
+}
+
+fn kernel_core(r: f32) -> f32 {
+    if r <= 0.0 || r >= 1.0 {
+        0.0
+    } else {
+        (4.0 - 1.0 / (r * (1.0 - r))).exp()
+    }
+}
+
+// Concentric smooth rings with the given peak heights, normalized to sum 1.
+fn ring_kernel(radius: f32, peaks: &[f32]) -> Vec<(i32, i32, f32)> {
+    let reach = radius.ceil() as i32;
+    let rings = peaks.len().max(1) as f32;
+    let mut taps = Vec::new();
+    for dy in -reach..=reach {
+        for dx in -reach..=reach {
+            let distance = ((dx * dx + dy * dy) as f32).sqrt() / radius;
+            if distance >= 1.0 {
+                continue;
+            }
+            let ring_position = distance * rings;
+            let ring = (ring_position as usize).min(peaks.len().saturating_sub(1));
+            let weight =
+                peaks.get(ring).copied().unwrap_or(1.0) * kernel_core(ring_position.fract());
+            if weight > 0.0 {
+                taps.push((dx, dy, weight));
+            }
+        }
+    }
+    let total: f32 = taps.iter().map(|(_, _, weight)| weight).sum();
+    if total > 0.0 {
+        for (_, _, weight) in taps.iter_mut() {
+            *weight /= total;
+        }
+    }
+    taps
+}
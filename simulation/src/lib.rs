@@ -0,0 +1,46 @@
+//! Headless simulation core of the Artificial Life Explorer.
+//!
+//! Nothing in here depends on the Bevy app or egui, so the model can be stepped
+//! from tests, command line tools or any other front-end.
+
+mod advection;
+pub mod boundary;
+mod channel;
+mod chemotaxis;
+mod convolution;
+mod domain;
+pub mod expression;
+mod fluid;
+mod grid;
+mod history;
+mod integrator;
+mod lenia;
+mod life;
+mod noise;
+mod parallel;
+mod parameter_map;
+pub mod reaction;
+pub mod render;
+mod seeder;
+mod stability;
+mod state;
+mod stencil;
+
+pub use advection::{Advection, AdvectionScheme, Flow};
+pub use boundary::Boundary;
+pub use channel::ChannelParameters;
+pub use chemotaxis::Chemotaxis;
+pub use domain::{CellKind, Domain};
+pub use fluid::Fluid;
+pub use grid::Grid;
+pub use history::History;
+pub use integrator::Integrator;
+pub use lenia::Lenia;
+pub use life::{LifeRule, LifeWorkspace, Neighbourhood, RuleError};
+pub use noise::Noise;
+pub use parameter_map::{MapProfile, ParameterMap, ParameterTarget};
+pub use reaction::{ParameterSpec, ReactionModel};
+pub use seeder::Seeder;
+pub use stability::{Instability, SATURATION_LIMIT};
+pub use state::{Automaton, Simulation};
+pub use stencil::{Kernel, Stencil};
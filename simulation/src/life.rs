@@ -0,0 +1,519 @@
+use std::{fmt, ops::RangeInclusive};
+
+use rand::Rng;
+
+use super::{
+    boundary::{Boundary, Neighbour},
+    grid::Grid,
+};
+
+// Ages are stored in the second channel in units of this many iterations.
+const AGE_SCALE: f32 = 100.0;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Neighbourhood {
+    Moore,
+    VonNeumann,
+}
+
+/// Outer totalistic rule covering Life-like (`B3/S23`), Generations
+/// (`B2/S/C3`) and Larger than Life (`R5,C0,M1,S34..58,B34..45,NM`) automata.
+///
+/// Channel 0 holds the state: 0 is dead, 1 alive, and the dying states of
+/// Generations rules fade from 1 towards 0. Channel 1 holds the age of living
+/// cells.
+#[derive(Clone, PartialEq)]
+pub struct LifeRule {
+    pub range: u32,
+    pub states: u32,
+    pub include_center: bool,
+    pub neighbourhood: Neighbourhood,
+    pub birth: Vec<RangeInclusive<u32>>,
+    pub survival: Vec<RangeInclusive<u32>>,
+}
+
+/// Buffers of the Life step, kept between steps.
+#[derive(Clone, Default)]
+pub struct LifeWorkspace {
+    cells: Vec<u32>,
+    alive: Vec<u32>,
+    counts: Vec<u32>,
+    // Summed area table of the Moore neighbourhood counts.
+    table: Vec<u32>,
+    birth: Vec<bool>,
+    survival: Vec<bool>,
+}
+
+#[derive(Clone, Debug)]
+pub struct RuleError(pub String);
+
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for RuleError {}
+
+impl Default for LifeRule {
+    fn default() -> Self {
+        Self {
+            range: 1,
+            states: 2,
+            include_center: false,
+            neighbourhood: Neighbourhood::Moore,
+            birth: vec![3..=3],
+            survival: vec![2..=3],
+        }
+    }
+}
+
+impl LifeRule {
+    /// State and age channels of a cell just born.
+    pub const NEWBORN: [f32; 2] = [1.0, 0.0];
+
+    pub fn parse(rule: &str) -> Result<Self, RuleError> {
+        let rule = rule.trim();
+        if rule.contains(',') {
+            Self::parse_larger_than_life(rule)
+        } else {
+            Self::parse_slashed(rule)
+        }
+    }
+
+    fn parse_slashed(rule: &str) -> Result<Self, RuleError> {
+        let parts: Vec<&str> = rule.split('/').map(str::trim).collect();
+        let mut parsed = Self {
+            birth: Vec::new(),
+            survival: Vec::new(),
+            ..Self::default()
+        };
+        let tagged = parts
+            .iter()
+            .any(|part| part.starts_with(char::is_alphabetic));
+        if tagged {
+            for part in parts {
+                let (tag, value) = part.split_at(part.chars().next().map_or(0, char::len_utf8));
+                match tag {
+                    "B" | "b" => parsed.birth = parse_digits(value)?,
+                    "S" | "s" => parsed.survival = parse_digits(value)?,
+                    "C" | "c" | "G" | "g" => parsed.states = parse_states(value)?,
+                    _ => return Err(RuleError(format!("unknown rule part '{}'", part))),
+                }
+            }
+        } else {
+            // Plain numeric notation: survival/birth[/states].
+            if !(2..=3).contains(&parts.len()) {
+                return Err(RuleError(format!(
+                    "expected survival/birth[/states] but found '{}'",
+                    rule
+                )));
+            }
+            parsed.survival = parse_digits(parts[0])?;
+            parsed.birth = parse_digits(parts[1])?;
+            if let Some(states) = parts.get(2) {
+                parsed.states = parse_states(states)?;
+            }
+        }
+        Ok(parsed)
+    }
+
+    fn parse_larger_than_life(rule: &str) -> Result<Self, RuleError> {
+        let mut parsed = Self {
+            birth: Vec::new(),
+            survival: Vec::new(),
+            ..Self::default()
+        };
+        for part in rule.split(',').map(str::trim) {
+            let mut characters = part.chars();
+            let tag = characters.next().map(|c| c.to_ascii_uppercase());
+            let value = characters.as_str();
+            match tag {
+                Some('R') => {
+                    parsed.range = value
+                        .parse()
+                        .ok()
+                        .filter(|range| (1..=50).contains(range))
+                        .ok_or_else(|| RuleError(format!("range must be 1 to 50 in '{}'", part)))?
+                }
+                Some('C') => parsed.states = parse_states(value)?,
+                Some('M') => {
+                    parsed.include_center = match value {
+                        "0" => false,
+                        "1" => true,
+                        _ => return Err(RuleError(format!("'{}' must be M0 or M1", part))),
+                    }
+                }
+                Some('S') => parsed.survival = parse_ranges(value, part)?,
+                Some('B') => parsed.birth = parse_ranges(value, part)?,
+                Some('N') => {
+                    parsed.neighbourhood = match value {
+                        "M" | "m" => Neighbourhood::Moore,
+                        "N" | "n" => Neighbourhood::VonNeumann,
+                        _ => return Err(RuleError(format!("'{}' must be NM or NN", part))),
+                    }
+                }
+                _ => return Err(RuleError(format!("unknown rule part '{}'", part))),
+            }
+        }
+        Ok(parsed)
+    }
+
+    fn larger_than_life(&self) -> bool {
+        self.range > 1 || self.include_center || self.neighbourhood != Neighbourhood::Moore
+    }
+
+    fn lookup(ranges: &[RangeInclusive<u32>], count: u32, table: &mut Vec<bool>) {
+        table.clear();
+        table.extend((0..=count).map(|n| ranges.iter().any(|range| range.contains(&n))));
+    }
+
+    fn max_count(&self) -> u32 {
+        let r = self.range;
+        let count = match self.neighbourhood {
+            Neighbourhood::Moore => (2 * r + 1) * (2 * r + 1),
+            Neighbourhood::VonNeumann => 2 * r * (r + 1) + 1,
+        };
+        if self.include_center {
+            count
+        } else {
+            count - 1
+        }
+    }
+
+    pub fn step(&self, grid: &mut Grid, boundary: Boundary, workspace: &mut LifeWorkspace) {
+        let (width, height) = (grid.width(), grid.height());
+        let states = self.states.max(2);
+        let LifeWorkspace {
+            cells,
+            alive,
+            counts,
+            table,
+            birth,
+            survival,
+        } = workspace;
+        cells.clear();
+        cells.extend(
+            grid.channel(0)
+                .iter()
+                .map(|value| decode_state(*value, states)),
+        );
+        alive.clear();
+        alive.extend(cells.iter().map(|state| (*state == 1) as u32));
+        let alive = alive.as_slice();
+        let alive_at = |x: i32, y: i32| match boundary.neighbour(x, y, width, height) {
+            Neighbour::Cell(x, y) => alive[y * width + x],
+            Neighbour::Value(value) => (decode_state(value, states) == 1) as u32,
+        };
+        let range = self.range as i32;
+        match self.neighbourhood {
+            Neighbourhood::Moore => moore_counts(alive_at, width, height, range, table, counts),
+            Neighbourhood::VonNeumann => von_neumann_counts(alive_at, width, height, range, counts),
+        }
+        let max_count = self.max_count();
+        Self::lookup(&self.birth, max_count, birth);
+        Self::lookup(&self.survival, max_count, survival);
+
+        // Every cell only reads its own age before overwriting it, so the
+        // grid is updated in place.
+        for (index, state) in cells.iter().enumerate() {
+            let (x, y) = (index % width, index / width);
+            let mut count = counts[index];
+            if !self.include_center {
+                count -= alive[index];
+            }
+            let count = count.min(max_count) as usize;
+            let next = match state {
+                0 if birth[count] => 1,
+                0 => 0,
+                1 if survival[count] => 1,
+                1 if states > 2 => 2,
+                1 => 0,
+                dying if dying + 1 < states => dying + 1,
+                _ => 0,
+            };
+            let age = match (state, next) {
+                (1, 1) => grid.get(x, y, 1) + 1.0 / AGE_SCALE,
+                _ => 0.0,
+            };
+            grid.set(x, y, 0, encode_state(next, states));
+            grid.set(x, y, 1, age);
+        }
+    }
+
+    /// Snaps the state channel back to the discrete states, e.g. after the
+    /// grid was resampled.
+    pub fn quantize(&self, grid: &mut Grid) {
+        let states = self.states.max(2);
+        for y in 0..grid.height() {
+            for x in 0..grid.width() {
+                let value = grid.get(x, y, 0);
+                let state = if value < 0.5 / (states - 1) as f32 {
+                    0
+                } else {
+                    decode_state(value, states)
+                };
+                grid.set(x, y, 0, encode_state(state, states));
+            }
+        }
+    }
+
+    pub fn randomize(grid: &mut Grid, density: f32, rng: &mut impl Rng) {
+        for y in 0..grid.height() {
+            for x in 0..grid.width() {
+                let alive = rng.gen::<f32>() < density;
+                grid.set(x, y, 0, if alive { 1.0 } else { 0.0 });
+                for channel in 1..grid.channels() {
+                    grid.set(x, y, channel, 0.0);
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for LifeRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.larger_than_life() {
+            let ranges = |ranges: &[RangeInclusive<u32>]| {
+                ranges
+                    .iter()
+                    .map(|range| format!("{}..{}", range.start(), range.end()))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            };
+            write!(
+                f,
+                "R{},C{},M{},S{},B{},N{}",
+                self.range,
+                self.states,
+                self.include_center as u8,
+                ranges(&self.survival),
+                ranges(&self.birth),
+                match self.neighbourhood {
+                    Neighbourhood::Moore => "M",
+                    Neighbourhood::VonNeumann => "N",
+                }
+            )
+        } else {
+            let digits = |ranges: &[RangeInclusive<u32>]| {
+                ranges
+                    .iter()
+                    .flat_map(|range| range.clone())
+                    .map(|count| count.to_string())
+                    .collect::<String>()
+            };
+            write!(f, "B{}/S{}", digits(&self.birth), digits(&self.survival))?;
+            if self.states > 2 {
+                write!(f, "/C{}", self.states)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+fn parse_digits(digits: &str) -> Result<Vec<RangeInclusive<u32>>, RuleError> {
+    digits
+        .chars()
+        .map(|digit| match digit.to_digit(10) {
+            Some(count) if count <= 8 => Ok(count..=count),
+            _ => Err(RuleError(format!(
+                "'{}' is not a neighbour count between 0 and 8",
+                digit
+            ))),
+        })
+        .collect()
+}
+
+fn parse_states(states: &str) -> Result<u32, RuleError> {
+    match states.parse::<u32>() {
+        // Golly writes C0 for plain two state Larger than Life rules.
+        Ok(0) => Ok(2),
+        Ok(states) if (2..=255).contains(&states) => Ok(states),
+        _ => Err(RuleError(format!(
+            "number of states must be 2 to 255, found '{}'",
+            states
+        ))),
+    }
+}
+
+fn parse_ranges(ranges: &str, part: &str) -> Result<Vec<RangeInclusive<u32>>, RuleError> {
+    let error = || RuleError(format!("expected 'min..max' in '{}'", part));
+    if ranges.is_empty() {
+        return Ok(Vec::new());
+    }
+    let (start, end) = match ranges.split_once("..") {
+        Some((start, end)) => (start, end),
+        None => (ranges, ranges),
+    };
+    let start: u32 = start.parse().map_err(|_| error())?;
+    let end: u32 = end.parse().map_err(|_| error())?;
+    Ok(vec![start..=end])
+}
+
+fn encode_state(state: u32, states: u32) -> f32 {
+    match state {
+        0 => 0.0,
+        _ => 1.0 - (state - 1) as f32 / (states - 1) as f32,
+    }
+}
+
+fn decode_state(value: f32, states: u32) -> u32 {
+    if value <= 0.0 {
+        0
+    } else {
+        (1 + ((1.0 - value.min(1.0)) * (states - 1) as f32).round() as u32).min(states - 1)
+    }
+}
+
+// Box sums over the (2r + 1)^2 square around every cell, through a summed
+// area table of the grid padded by r on every side.
+fn moore_counts(
+    alive_at: impl Fn(i32, i32) -> u32,
+    width: usize,
+    height: usize,
+    range: i32,
+    table: &mut Vec<u32>,
+    counts: &mut Vec<u32>,
+) {
+    let padded_width = width + 2 * range as usize + 1;
+    let padded_height = height + 2 * range as usize + 1;
+    table.clear();
+    table.resize(padded_width * padded_height, 0);
+    let table = table.as_mut_slice();
+    for py in 1..padded_height {
+        let mut row_sum = 0;
+        for px in 1..padded_width {
+            row_sum += alive_at(px as i32 - 1 - range, py as i32 - 1 - range);
+            table[py * padded_width + px] = table[(py - 1) * padded_width + px] + row_sum;
+        }
+    }
+    let side = 2 * range as usize + 1;
+    counts.resize(width * height, 0);
+    let counts = counts.as_mut_slice();
+    for y in 0..height {
+        for x in 0..width {
+            let (x0, y0, x1, y1) = (x, y, x + side, y + side);
+            counts[y * width + x] = table[y1 * padded_width + x1] + table[y0 * padded_width + x0]
+                - table[y0 * padded_width + x1]
+                - table[y1 * padded_width + x0];
+        }
+    }
+}
+
+fn von_neumann_counts(
+    alive_at: impl Fn(i32, i32) -> u32,
+    width: usize,
+    height: usize,
+    range: i32,
+    counts: &mut Vec<u32>,
+) {
+    counts.resize(width * height, 0);
+    for y in 0..height as i32 {
+        for x in 0..width as i32 {
+            let mut count = 0;
+            for dy in -range..=range {
+                let reach = range - dy.abs();
+                for dx in -reach..=reach {
+                    count += alive_at(x + dx, y + dy);
+                }
+            }
+            counts[y as usize * width + x as usize] = count;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alive(grid: &Grid) -> Vec<(usize, usize)> {
+        let mut cells = Vec::new();
+        for y in 0..grid.height() {
+            for x in 0..grid.width() {
+                if grid.get(x, y, 0) == 1.0 {
+                    cells.push((x, y));
+                }
+            }
+        }
+        cells
+    }
+
+    #[test]
+    fn parses_life_like_rules() {
+        let life = LifeRule::parse("B3/S23").unwrap();
+        assert_eq!(life.birth, vec![3..=3]);
+        assert_eq!(life.survival, vec![2..=2, 3..=3]);
+        assert_eq!((life.range, life.states), (1, 2));
+        assert_eq!(life.to_string(), "B3/S23");
+        assert!(LifeRule::parse(" 23/3 ").unwrap() == life);
+        let highlife = LifeRule::parse("b36/s23").unwrap();
+        assert_eq!(highlife.birth, vec![3..=3, 6..=6]);
+    }
+
+    #[test]
+    fn parses_generations_rules() {
+        let brain = LifeRule::parse("B2/S/C3").unwrap();
+        assert_eq!(brain.states, 3);
+        assert_eq!(brain.birth, vec![2..=2]);
+        assert!(brain.survival.is_empty());
+        assert_eq!(brain.to_string(), "B2/S/C3");
+        assert_eq!(LifeRule::parse("345/2/4").unwrap().states, 4);
+    }
+
+    #[test]
+    fn parses_larger_than_life_rules() {
+        let bugs = LifeRule::parse("R5,C0,M1,S34..58,B34..45,NM").unwrap();
+        assert_eq!(bugs.range, 5);
+        assert_eq!(bugs.states, 2);
+        assert!(bugs.include_center);
+        assert!(bugs.neighbourhood == Neighbourhood::Moore);
+        assert_eq!(bugs.survival, vec![34..=58]);
+        assert_eq!(bugs.birth, vec![34..=45]);
+        assert!(LifeRule::parse(&bugs.to_string()).unwrap() == bugs);
+        let diamond = LifeRule::parse("R2,C3,M0,S2..4,B3,NN").unwrap();
+        assert!(diamond.neighbourhood == Neighbourhood::VonNeumann);
+        assert_eq!(diamond.birth, vec![3..=3]);
+    }
+
+    #[test]
+    fn rejects_malformed_rules() {
+        for rule in [
+            "B9/S23",
+            "X3/S23",
+            "B3/Sx",
+            "23",
+            "1/2/3/4",
+            "B3/S23/C1",
+            "B3/S23/C256",
+            "R0,C0,M0,S1..2,B1..2,NM",
+            "R51,C0,M0,S1..2,B1..2,NM",
+            "R2,M2",
+            "R2,S3..x",
+            "R2,NX",
+            "R2,Q1",
+        ] {
+            assert!(
+                LifeRule::parse(rule).is_err(),
+                "'{}' should not parse",
+                rule
+            );
+        }
+    }
+
+    #[test]
+    fn blinker_oscillates() {
+        let rule = LifeRule::default();
+        let mut workspace = LifeWorkspace::default();
+        let mut grid = Grid::new(5, 5, 2);
+        for x in 1..=3 {
+            grid.set(x, 2, 0, 1.0);
+        }
+        rule.step(&mut grid, Boundary::Periodic, &mut workspace);
+        assert_eq!(alive(&grid), vec![(2, 1), (2, 2), (2, 3)]);
+        // Only the centre survived, so only it has aged.
+        assert_eq!(grid.get(2, 2, 1), 1.0 / AGE_SCALE);
+        assert_eq!(grid.get(2, 1, 1), 0.0);
+        rule.step(&mut grid, Boundary::Dirichlet(0.0), &mut workspace);
+        assert_eq!(alive(&grid), vec![(1, 2), (2, 2), (3, 2)]);
+    }
+}
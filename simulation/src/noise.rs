@@ -0,0 +1,106 @@
+use rand::Rng;
+
+use super::grid::Grid;
+
+// Gaussian smoothing kernels are cut off at this many correlation lengths.
+const KERNEL_REACH: f32 = 3.0;
+
+/// Stochastic forcing of one channel: `(additive + multiplicative * c) * dW`,
+/// where `dW` is Gaussian noise smoothed over `correlation_length` cells.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct Noise {
+    pub additive: f32,
+    pub multiplicative: f32,
+    pub correlation_length: f32,
+}
+
+impl Noise {
+    pub fn is_active(&self) -> bool {
+        self.additive != 0.0 || self.multiplicative != 0.0
+    }
+}
+
+/// Buffers for the noise field, kept between steps.
+#[derive(Clone, Default)]
+pub struct NoiseField {
+    white: Vec<f32>,
+    smoothed: Vec<f32>,
+    kernel: Vec<f32>,
+    kernel_length: f32,
+}
+
+impl NoiseField {
+    /// Adds the noise of one channel over a step of length `dt`
+    /// (Euler-Maruyama).
+    pub fn apply(
+        &mut self,
+        grid: &mut Grid,
+        channel: usize,
+        noise: &Noise,
+        dt: f32,
+        rng: &mut impl Rng,
+    ) {
+        let (width, height) = (grid.width(), grid.height());
+        self.white.clear();
+        self.white
+            .extend((0..width * height).map(|_| standard_normal(rng)));
+        if noise.correlation_length > 0.0 {
+            self.smooth(width, height, noise.correlation_length);
+        }
+        let scale = dt.sqrt();
+        for (value, dw) in grid.channel_mut(channel).iter_mut().zip(&self.white) {
+            *value += (noise.additive + noise.multiplicative * *value) * scale * dw;
+        }
+    }
+
+    // Separable Gaussian blur of the white noise on the torus, scaled back to
+    // unit variance.
+    fn smooth(&mut self, width: usize, height: usize, length: f32) {
+        if self.kernel_length != length {
+            let reach = (KERNEL_REACH * length).ceil() as i32;
+            self.kernel = (-reach..=reach)
+                .map(|d| (-0.5 * (d as f32 / length).powi(2)).exp())
+                .collect();
+            let norm = self.kernel.iter().map(|w| w * w).sum::<f32>().sqrt();
+            for weight in self.kernel.iter_mut() {
+                *weight /= norm;
+            }
+            self.kernel_length = length;
+        }
+        let reach = (self.kernel.len() / 2) as i32;
+        self.smoothed.resize(width * height, 0.0);
+        for y in 0..height {
+            for x in 0..width {
+                self.smoothed[y * width + x] = self
+                    .kernel
+                    .iter()
+                    .enumerate()
+                    .map(|(i, weight)| {
+                        let sx = (x as i32 + i as i32 - reach).rem_euclid(width as i32) as usize;
+                        weight * self.white[y * width + sx]
+                    })
+                    .sum();
+            }
+        }
+        for y in 0..height {
+            for x in 0..width {
+                self.white[y * width + x] = self
+                    .kernel
+                    .iter()
+                    .enumerate()
+                    .map(|(i, weight)| {
+                        let sy = (y as i32 + i as i32 - reach).rem_euclid(height as i32) as usize;
+                        weight * self.smoothed[sy * width + x]
+                    })
+                    .sum();
+            }
+        }
+    }
+}
+
+// Box-Muller transform of two uniform samples.
+fn standard_normal(rng: &mut impl Rng) -> f32 {
+    let u: f32 = 1.0 - rng.gen::<f32>();
+    let v: f32 = rng.gen();
+    (-2.0 * u.ln()).sqrt() * (std::f32::consts::TAU * v).cos()
+}
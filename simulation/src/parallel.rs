@@ -0,0 +1,33 @@
+#[cfg(not(target_arch = "wasm32"))]
+use bevy_tasks::{ComputeTaskPool, TaskPool};
+
+// Bands per worker thread, so that uneven bands still keep every core busy.
+#[cfg(not(target_arch = "wasm32"))]
+const BANDS_PER_THREAD: usize = 4;
+
+/// Runs `update` on every band of `bands`, spread over Bevy's compute task
+/// pool. On wasm, where there is a single thread, the bands run in order.
+/// Bands may come zipped with buffers of their own.
+pub fn for_each_band<T: Send>(bands: impl Iterator<Item = T>, update: impl Fn(T) + Send + Sync) {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let pool = ComputeTaskPool::get_or_init(TaskPool::default);
+        let update = &update;
+        pool.scope(|scope| {
+            for band in bands {
+                scope.spawn(async move { update(band) });
+            }
+        });
+    }
+    #[cfg(target_arch = "wasm32")]
+    bands.for_each(update);
+}
+
+/// Number of rows per band to split `height` rows over the available threads.
+pub fn band_rows(height: usize) -> usize {
+    #[cfg(not(target_arch = "wasm32"))]
+    let bands = ComputeTaskPool::get_or_init(TaskPool::default).thread_num() * BANDS_PER_THREAD;
+    #[cfg(target_arch = "wasm32")]
+    let bands = 1;
+    ((height + bands - 1) / bands.max(1)).max(1)
+}
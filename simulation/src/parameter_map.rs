@@ -0,0 +1,115 @@
+use super::{channel::ChannelParameters, grid::Grid};
+
+/// Reaction-diffusion parameter replaced by a map.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParameterTarget {
+    /// Parameter shared by all channels, index into `Simulation::parameters`.
+    Global(usize),
+    /// Diffusion coefficient of a channel.
+    Diffusion(usize),
+    /// Parameter `index` of channel `channel`.
+    Channel { channel: usize, index: usize },
+}
+
+impl ParameterTarget {
+    pub(crate) fn value_mut<'a>(
+        &self,
+        parameters: &'a mut [f32],
+        channels: &'a mut [ChannelParameters],
+    ) -> Option<&'a mut f32> {
+        match *self {
+            Self::Global(index) => parameters.get_mut(index),
+            Self::Diffusion(channel) => channels
+                .get_mut(channel)
+                .map(|channel| &mut channel.diffusion_coefficient),
+            Self::Channel { channel, index } => channels
+                .get_mut(channel)
+                .and_then(|channel| channel.values.get_mut(index)),
+        }
+    }
+}
+
+/// Shape of a parameter map, a weight between 0 and 1 for every cell.
+#[derive(Clone)]
+pub enum MapProfile {
+    /// Linear ramp across the grid, rising along `angle` (radians).
+    Gradient { angle: f32 },
+    /// Ramp from the centre of the grid out to the middle of its edges.
+    Radial,
+    /// Grey levels of an image, stretched over the grid.
+    Image(Grid),
+    /// Weights painted with the brush, one per cell.
+    Painted(Grid),
+}
+
+impl MapProfile {
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Gradient { .. } => "Gradient",
+            Self::Radial => "Radial",
+            Self::Image(_) => "Image",
+            Self::Painted(_) => "Painted",
+        }
+    }
+
+    fn weight(&self, x: usize, y: usize, width: usize, height: usize) -> f32 {
+        // Cell centres, from -0.5 to 0.5 across the grid.
+        let u = (x as f32 + 0.5) / width as f32 - 0.5;
+        let v = (y as f32 + 0.5) / height as f32 - 0.5;
+        match self {
+            Self::Gradient { angle } => {
+                let (sin, cos) = angle.sin_cos();
+                let extent = cos.abs() + sin.abs();
+                0.5 + (u * cos + v * sin) / extent
+            }
+            Self::Radial => (2.0 * (u * u + v * v).sqrt()).min(1.0),
+            Self::Image(image) | Self::Painted(image) => image.sample(x, y, width, height, 0),
+        }
+    }
+}
+
+/// A parameter that varies over the grid, interpolating between `from` where
+/// the profile is 0 and `to` where it is 1.
+#[derive(Clone)]
+pub struct ParameterMap {
+    pub target: ParameterTarget,
+    pub profile: MapProfile,
+    pub from: f32,
+    pub to: f32,
+}
+
+impl ParameterMap {
+    pub fn new(target: ParameterTarget, from: f32, to: f32) -> Self {
+        Self {
+            target,
+            profile: MapProfile::Gradient { angle: 0.0 },
+            from,
+            to,
+        }
+    }
+
+    pub fn value(&self, x: usize, y: usize, width: usize, height: usize) -> f32 {
+        let weight = self.profile.weight(x, y, width, height).clamp(0.0, 1.0);
+        self.from + (self.to - self.from) * weight
+    }
+
+    /// Largest magnitude the map takes anywhere.
+    pub fn max_abs(&self) -> f32 {
+        self.from.abs().max(self.to.abs())
+    }
+
+    /// Writes the value of every cell of a `width` x `height` grid, row by row.
+    pub(crate) fn bake(&self, width: usize, height: usize, values: &mut Vec<f32>) {
+        values.clear();
+        for y in 0..height {
+            values.extend((0..width).map(|x| self.value(x, y, width, height)));
+        }
+    }
+
+    /// Keeps painted weights aligned with the cells after a resize.
+    pub(crate) fn resize(&mut self, width: usize, height: usize) {
+        if let MapProfile::Painted(weights) = &mut self.profile {
+            *weights = weights.resampled(width, height);
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use std::{ops::RangeInclusive, sync::Arc};
+
+use super::channel::ChannelParameters;
+
+mod brusselator;
+mod custom;
+mod cyclic_holling;
+mod fitzhugh_nagumo;
+mod gierer_meinhardt;
+mod gray_scott;
+mod lotka_volterra;
+mod schnakenberg;
+
+pub use brusselator::Brusselator;
+pub use custom::{CustomModel, EquationError, CHANNEL_VARIABLES};
+pub use cyclic_holling::CyclicHolling;
+pub use fitzhugh_nagumo::FitzHughNagumo;
+pub use gierer_meinhardt::GiererMeinhardt;
+pub use gray_scott::GrayScott;
+pub use lotka_volterra::CyclicLotkaVolterra;
+pub use schnakenberg::Schnakenberg;
+
+#[derive(Clone)]
+pub struct ParameterSpec {
+    pub name: String,
+    pub range: RangeInclusive<f32>,
+    pub default: f32,
+}
+
+impl ParameterSpec {
+    pub fn new(name: &str, range: RangeInclusive<f32>, default: f32) -> Self {
+        Self {
+            name: name.to_owned(),
+            range,
+            default,
+        }
+    }
+}
+
+/// Local reaction term of a reaction-diffusion system.
+///
+/// A model declares its parameters, both the ones shared by the whole system
+/// and the ones owned by each channel, so that front-ends can present them
+/// without knowing the model.
+pub trait ReactionModel: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Number of species the model is defined for, `None` if it works with any.
+    fn channel_count(&self) -> Option<usize>;
+
+    fn channel_name(&self, _channel: usize) -> Option<String> {
+        None
+    }
+
+    fn parameters(&self) -> Vec<ParameterSpec> {
+        Vec::new()
+    }
+
+    // The per-channel specs are also asked for channels past
+    // `channel_count`, added while another automaton runs, so they must not
+    // panic on them.
+    fn channel_parameters(&self, channel: usize) -> Vec<ParameterSpec>;
+
+    fn diffusion(&self, channel: usize) -> ParameterSpec;
+
+    /// Whether concentrations are clamped at zero after every step.
+    fn non_negative(&self) -> bool {
+        true
+    }
+
+    /// Writes the rate of change of every channel of `cell` into `rates`.
+    fn react(
+        &self,
+        cell: &[f32],
+        parameters: &[f32],
+        channels: &[ChannelParameters],
+        rates: &mut [f32],
+    );
+}
+
+pub fn builtin_models() -> Vec<Arc<dyn ReactionModel>> {
+    vec![
+        Arc::new(CyclicHolling),
+        Arc::new(GrayScott),
+        Arc::new(Brusselator),
+        Arc::new(FitzHughNagumo),
+        Arc::new(Schnakenberg),
+        Arc::new(GiererMeinhardt),
+        Arc::new(CyclicLotkaVolterra),
+    ]
+}
@@ -0,0 +1,47 @@
+use super::{ChannelParameters, ParameterSpec, ReactionModel};
+
+pub struct Brusselator;
+
+impl ReactionModel for Brusselator {
+    fn name(&self) -> &str {
+        "Brusselator"
+    }
+
+    fn channel_count(&self) -> Option<usize> {
+        Some(2)
+    }
+
+    fn channel_name(&self, channel: usize) -> Option<String> {
+        ["U", "V"].get(channel).map(|name| (*name).to_owned())
+    }
+
+    fn parameters(&self) -> Vec<ParameterSpec> {
+        vec![
+            ParameterSpec::new("A", 0.0..=3.0, 1.0),
+            ParameterSpec::new("B", 0.0..=5.0, 1.9),
+            ParameterSpec::new("Rate", 0.0..=0.5, 0.1),
+        ]
+    }
+
+    fn channel_parameters(&self, _channel: usize) -> Vec<ParameterSpec> {
+        Vec::new()
+    }
+
+    fn diffusion(&self, channel: usize) -> ParameterSpec {
+        let default = [0.02, 0.16].get(channel).copied().unwrap_or(0.0);
+        ParameterSpec::new("Diffusion", 0.0..=0.2, default)
+    }
+
+    fn react(
+        &self,
+        cell: &[f32],
+        parameters: &[f32],
+        _channels: &[ChannelParameters],
+        rates: &mut [f32],
+    ) {
+        let (u, v) = (cell[0], cell[1]);
+        let (a, b, rate) = (parameters[0], parameters[1], parameters[2]);
+        rates[0] = rate * (a - (b + 1.0) * u + u * u * v);
+        rates[1] = rate * (b * u - u * u * v);
+    }
+}
@@ -0,0 +1,94 @@
+use std::fmt;
+
+use super::{ChannelParameters, ParameterSpec, ReactionModel};
+use crate::expression::{Expression, ParseError};
+
+pub const CHANNEL_VARIABLES: [&str; 6] = ["u", "v", "w", "x", "y", "z"];
+
+#[derive(Clone, Debug)]
+pub struct EquationError {
+    pub channel: usize,
+    pub error: ParseError,
+}
+
+impl fmt::Display for EquationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "d{}/dt, {}", CHANNEL_VARIABLES[self.channel], self.error)
+    }
+}
+
+impl std::error::Error for EquationError {}
+
+/// Reaction model whose rates are typed in by the user, one equation per
+/// channel. Every identifier that is neither a channel variable nor a
+/// function becomes a parameter of the model.
+#[derive(Clone)]
+pub struct CustomModel {
+    equations: Vec<Expression>,
+    parameter_names: Vec<String>,
+}
+
+impl CustomModel {
+    pub fn compile(sources: &[String]) -> Result<Self, EquationError> {
+        let variables = &CHANNEL_VARIABLES[..sources.len().min(CHANNEL_VARIABLES.len())];
+        let mut parameter_names = Vec::new();
+        let equations = sources
+            .iter()
+            .take(variables.len())
+            .enumerate()
+            .map(|(channel, source)| {
+                Expression::parse(source, variables, &mut parameter_names)
+                    .map_err(|error| EquationError { channel, error })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self {
+            equations,
+            parameter_names,
+        })
+    }
+
+    pub fn parameter_names(&self) -> &[String] {
+        &self.parameter_names
+    }
+}
+
+impl ReactionModel for CustomModel {
+    fn name(&self) -> &str {
+        "Custom"
+    }
+
+    fn channel_count(&self) -> Option<usize> {
+        Some(self.equations.len())
+    }
+
+    fn channel_name(&self, channel: usize) -> Option<String> {
+        Some(CHANNEL_VARIABLES[channel].to_owned())
+    }
+
+    fn parameters(&self) -> Vec<ParameterSpec> {
+        self.parameter_names
+            .iter()
+            .map(|name| ParameterSpec::new(name, 0.0..=5.0, 1.0))
+            .collect()
+    }
+
+    fn channel_parameters(&self, _channel: usize) -> Vec<ParameterSpec> {
+        Vec::new()
+    }
+
+    fn diffusion(&self, _channel: usize) -> ParameterSpec {
+        ParameterSpec::new("Diffusion", 0.0..=0.2, 0.05)
+    }
+
+    fn react(
+        &self,
+        cell: &[f32],
+        parameters: &[f32],
+        _channels: &[ChannelParameters],
+        rates: &mut [f32],
+    ) {
+        for (rate, equation) in rates.iter_mut().zip(&self.equations) {
+            *rate = equation.evaluate(cell, parameters);
+        }
+    }
+}
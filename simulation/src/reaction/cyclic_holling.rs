@@ -0,0 +1,60 @@
+use super::{ChannelParameters, ParameterSpec, ReactionModel};
+
+// Logistic growth with a saturating (Holling type III like) loss to the next
+// channel and a feedback driven by the previous one. For three channels this
+// is the red -> green -> blue -> red cycle the explorer started with.
+pub struct CyclicHolling;
+
+const PRESETS: [[f32; 5]; 3] = [
+    [0.02248, 0.98204, 3.195, 0.112, 0.458],
+    [0.08454, 0.92762, 4.666, 0.62, 0.981],
+    [0.05918, 0.92696, 2.015, 0.465, 0.727],
+];
+
+impl ReactionModel for CyclicHolling {
+    fn name(&self) -> &str {
+        "Cyclic Holling"
+    }
+
+    fn channel_count(&self) -> Option<usize> {
+        None
+    }
+
+    fn channel_parameters(&self, channel: usize) -> Vec<ParameterSpec> {
+        let preset = PRESETS[channel % PRESETS.len()];
+        vec![
+            ParameterSpec::new("Growth", 0.7..=1.0, preset[1]),
+            ParameterSpec::new("Interaction", 0.0..=5.0, preset[2]),
+            ParameterSpec::new("Saturation", 0.0..=3.0, preset[3]),
+            ParameterSpec::new("Feedback", 0.0..=3.0, preset[4]),
+        ]
+    }
+
+    fn diffusion(&self, channel: usize) -> ParameterSpec {
+        ParameterSpec::new("Diffusion", 0.0..=0.1, PRESETS[channel % PRESETS.len()][0])
+    }
+
+    fn react(
+        &self,
+        cell: &[f32],
+        _parameters: &[f32],
+        channels: &[ChannelParameters],
+        rates: &mut [f32],
+    ) {
+        let count = cell.len();
+        for (channel, rate) in rates.iter_mut().enumerate() {
+            let [growth_rate, interaction_coefficient, saturation_constant, feedback_coefficient] =
+                channels[channel].values[..]
+            else {
+                continue;
+            };
+            let own = cell[channel];
+            let next = cell[(channel + 1) % count];
+            let previous = cell[(channel + count - 1) % count];
+            *rate = growth_rate * own * (1.0 - own)
+                - ((interaction_coefficient * own * next)
+                    / (1.0 + saturation_constant * own * own))
+                + feedback_coefficient * (next - own) * previous * previous;
+        }
+    }
+}
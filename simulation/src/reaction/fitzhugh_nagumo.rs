@@ -0,0 +1,59 @@
+use super::{ChannelParameters, ParameterSpec, ReactionModel};
+
+// Excitable-media form with the cubic `u (1 - u) (u - a)`, which keeps the
+// voltage roughly inside [0, 1] instead of the symmetric [-2, 2] of the
+// original formulation.
+pub struct FitzHughNagumo;
+
+impl ReactionModel for FitzHughNagumo {
+    fn name(&self) -> &str {
+        "FitzHugh-Nagumo"
+    }
+
+    fn channel_count(&self) -> Option<usize> {
+        Some(2)
+    }
+
+    fn channel_name(&self, channel: usize) -> Option<String> {
+        ["Voltage", "Recovery"]
+            .get(channel)
+            .map(|name| (*name).to_owned())
+    }
+
+    fn parameters(&self) -> Vec<ParameterSpec> {
+        vec![
+            ParameterSpec::new("Threshold", -0.2..=0.5, 0.05),
+            ParameterSpec::new("Epsilon", 0.0..=0.1, 0.01),
+            ParameterSpec::new("Gamma", 0.0..=3.0, 1.0),
+            ParameterSpec::new("Rate", 0.0..=0.5, 0.4),
+        ]
+    }
+
+    fn channel_parameters(&self, _channel: usize) -> Vec<ParameterSpec> {
+        Vec::new()
+    }
+
+    fn diffusion(&self, channel: usize) -> ParameterSpec {
+        let default = [0.1, 0.0].get(channel).copied().unwrap_or(0.0);
+        ParameterSpec::new("Diffusion", 0.0..=0.2, default)
+    }
+
+    fn non_negative(&self) -> bool {
+        false
+    }
+
+    fn react(
+        &self,
+        cell: &[f32],
+        parameters: &[f32],
+        _channels: &[ChannelParameters],
+        rates: &mut [f32],
+    ) {
+        let (u, v) = (cell[0], cell[1]);
+        let [threshold, epsilon, gamma, rate] = parameters[..] else {
+            return;
+        };
+        rates[0] = rate * (u * (1.0 - u) * (u - threshold) - v);
+        rates[1] = rate * epsilon * (u - gamma * v);
+    }
+}
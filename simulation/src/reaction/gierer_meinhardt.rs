@@ -0,0 +1,63 @@
+use super::{ChannelParameters, ParameterSpec, ReactionModel};
+
+pub struct GiererMeinhardt;
+
+impl ReactionModel for GiererMeinhardt {
+    fn name(&self) -> &str {
+        "Gierer-Meinhardt"
+    }
+
+    fn channel_count(&self) -> Option<usize> {
+        Some(2)
+    }
+
+    fn channel_name(&self, channel: usize) -> Option<String> {
+        ["Activator", "Inhibitor"]
+            .get(channel)
+            .map(|name| (*name).to_owned())
+    }
+
+    fn parameters(&self) -> Vec<ParameterSpec> {
+        vec![
+            ParameterSpec::new("Activator Decay", 0.0..=2.0, 1.0),
+            ParameterSpec::new("Inhibitor Decay", 0.0..=3.0, 1.2),
+            ParameterSpec::new("Saturation", 0.0..=1.0, 0.1),
+            ParameterSpec::new("Basal Production", 0.0..=0.1, 0.01),
+            ParameterSpec::new("Inhibitor Basal Production", 0.0..=0.1, 0.01),
+            ParameterSpec::new("Inhibition Constant", 0.01..=1.0, 0.1),
+            ParameterSpec::new("Rate", 0.0..=0.5, 0.1),
+        ]
+    }
+
+    fn channel_parameters(&self, _channel: usize) -> Vec<ParameterSpec> {
+        Vec::new()
+    }
+
+    fn diffusion(&self, channel: usize) -> ParameterSpec {
+        let default = [0.004, 0.15].get(channel).copied().unwrap_or(0.0);
+        ParameterSpec::new("Diffusion", 0.0..=0.2, default)
+    }
+
+    fn react(
+        &self,
+        cell: &[f32],
+        parameters: &[f32],
+        _channels: &[ChannelParameters],
+        rates: &mut [f32],
+    ) {
+        let (activator, inhibitor) = (cell[0], cell[1]);
+        let [activator_decay, inhibitor_decay, saturation, basal_production, inhibitor_basal_production, inhibition_constant, rate] =
+            parameters[..]
+        else {
+            return;
+        };
+        let production = activator * activator;
+        // The inhibition constant keeps the production finite where the
+        // inhibitor runs out, as it does in freshly seeded fields.
+        rates[0] = rate
+            * (production / ((inhibition_constant + inhibitor) * (1.0 + saturation * production))
+                - activator_decay * activator
+                + basal_production);
+        rates[1] = rate * (production - inhibitor_decay * inhibitor + inhibitor_basal_production);
+    }
+}
@@ -0,0 +1,46 @@
+use super::{ChannelParameters, ParameterSpec, ReactionModel};
+
+pub struct GrayScott;
+
+impl ReactionModel for GrayScott {
+    fn name(&self) -> &str {
+        "Gray-Scott"
+    }
+
+    fn channel_count(&self) -> Option<usize> {
+        Some(2)
+    }
+
+    fn channel_name(&self, channel: usize) -> Option<String> {
+        ["U", "V"].get(channel).map(|name| (*name).to_owned())
+    }
+
+    fn parameters(&self) -> Vec<ParameterSpec> {
+        vec![
+            ParameterSpec::new("Feed", 0.0..=0.1, 0.055),
+            ParameterSpec::new("Kill", 0.0..=0.1, 0.062),
+        ]
+    }
+
+    fn channel_parameters(&self, _channel: usize) -> Vec<ParameterSpec> {
+        Vec::new()
+    }
+
+    fn diffusion(&self, channel: usize) -> ParameterSpec {
+        let default = [0.124, 0.062].get(channel).copied().unwrap_or(0.0);
+        ParameterSpec::new("Diffusion", 0.0..=0.2, default)
+    }
+
+    fn react(
+        &self,
+        cell: &[f32],
+        parameters: &[f32],
+        _channels: &[ChannelParameters],
+        rates: &mut [f32],
+    ) {
+        let (u, v) = (cell[0], cell[1]);
+        let (feed, kill) = (parameters[0], parameters[1]);
+        rates[0] = -u * v * v + feed * (1.0 - u);
+        rates[1] = u * v * v - (feed + kill) * v;
+    }
+}
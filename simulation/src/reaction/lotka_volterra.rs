@@ -0,0 +1,48 @@
+use super::{ChannelParameters, ParameterSpec, ReactionModel};
+
+// May-Leonard competition: every species is suppressed by its successor with
+// strength alpha and by its predecessor with strength beta.
+pub struct CyclicLotkaVolterra;
+
+impl ReactionModel for CyclicLotkaVolterra {
+    fn name(&self) -> &str {
+        "Cyclic Lotka-Volterra"
+    }
+
+    fn channel_count(&self) -> Option<usize> {
+        None
+    }
+
+    fn parameters(&self) -> Vec<ParameterSpec> {
+        vec![
+            ParameterSpec::new("Alpha", 0.0..=2.0, 0.8),
+            ParameterSpec::new("Beta", 0.0..=2.0, 1.3),
+        ]
+    }
+
+    fn channel_parameters(&self, _channel: usize) -> Vec<ParameterSpec> {
+        vec![ParameterSpec::new("Growth", 0.0..=1.0, 0.2)]
+    }
+
+    fn diffusion(&self, _channel: usize) -> ParameterSpec {
+        ParameterSpec::new("Diffusion", 0.0..=0.2, 0.05)
+    }
+
+    fn react(
+        &self,
+        cell: &[f32],
+        parameters: &[f32],
+        channels: &[ChannelParameters],
+        rates: &mut [f32],
+    ) {
+        let count = cell.len();
+        let (alpha, beta) = (parameters[0], parameters[1]);
+        for (channel, rate) in rates.iter_mut().enumerate() {
+            let own = cell[channel];
+            let next = cell[(channel + 1) % count];
+            let previous = cell[(channel + count - 1) % count];
+            *rate =
+                channels[channel].values[0] * own * (1.0 - own - alpha * next - beta * previous);
+        }
+    }
+}
@@ -0,0 +1,92 @@
+//! Conversion of the field into the image and height map buffers the
+//! front-end displays, kept here so they can be benchmarked headless.
+
+use super::{
+    boundary::{Boundary, Neighbour},
+    grid::Grid,
+};
+
+/// Mixes the channels of `grid`, each weighted by its colour, into one pixel
+/// per cell, built by `pixel` from the red, green and blue bytes.
+pub fn write_colors<P>(
+    grid: &Grid,
+    channel_colors: &[[f32; 3]],
+    pixels: &mut [P],
+    pixel: impl Fn(u8, u8, u8) -> P,
+) {
+    let planes: Vec<&[f32]> = (0..grid.channels()).map(|c| grid.channel(c)).collect();
+    for (index, output) in pixels.iter_mut().enumerate() {
+        let mut rgb = [0.0f32; 3];
+        for (plane, color) in planes.iter().zip(channel_colors) {
+            let concentration = plane[index].clamp(0.0, 1.0);
+            for (component, weight) in rgb.iter_mut().zip(color) {
+                *component += concentration * weight;
+            }
+        }
+        let [r, g, b] = rgb;
+        *output = pixel(display_value(r), display_value(g), display_value(b));
+    }
+}
+
+fn display_value(concentration: f32) -> u8 {
+    (concentration.clamp(0.0, 1.0) * 255.0) as u8
+}
+
+/// Lifts every vertex of a mesh laid out like the grid to the value of
+/// `render_channel`, or the mean of all channels.
+pub fn write_heights(vertices: &mut [[f32; 3]], grid: &Grid, render_channel: Option<usize>) {
+    match render_channel {
+        Some(channel) => {
+            for (vertex, value) in vertices.iter_mut().zip(grid.channel(channel)) {
+                vertex[1] = 0.5 * value.clamp(0.0, 1.0);
+            }
+        }
+        None => {
+            for vertex in vertices.iter_mut() {
+                vertex[1] = 0.0;
+            }
+            for channel in 0..grid.channels() {
+                for (vertex, value) in vertices.iter_mut().zip(grid.channel(channel)) {
+                    vertex[1] += value;
+                }
+            }
+            for vertex in vertices.iter_mut() {
+                vertex[1] = 0.5 * (vertex[1] / grid.channels() as f32).clamp(0.0, 1.0);
+            }
+        }
+    }
+}
+
+/// Normals of a `width` x `height` height map from the central differences
+/// of its heights; `depth` is the last component before normalizing, the
+/// larger the flatter the shading.
+pub fn write_normals(
+    normals: &mut [[f32; 3]],
+    vertices: &[[f32; 3]],
+    width: usize,
+    height: usize,
+    boundary: Boundary,
+    depth: f32,
+) {
+    let height_at = |x: i32, y: i32| {
+        if (0..width as i32).contains(&x) && (0..height as i32).contains(&y) {
+            return vertices[x as usize + width * y as usize][1];
+        }
+        match boundary.neighbour(x, y, width, height) {
+            Neighbour::Cell(x, y) => vertices[x + width * y][1],
+            Neighbour::Value(value) => 0.5 * value.clamp(0.0, 1.0),
+        }
+    };
+
+    for y in 0..(height as i32) {
+        for x in 0..(width as i32) {
+            let h_l = height_at(x - 1, y);
+            let h_r = height_at(x + 1, y);
+            let h_d = height_at(x, y - 1);
+            let h_u = height_at(x, y + 1);
+            let normal = [h_l - h_r, h_d - h_u, depth];
+            let length = normal.iter().map(|c| c * c).sum::<f32>().sqrt();
+            normals[x as usize + width * y as usize] = normal.map(|c| c / length);
+        }
+    }
+}
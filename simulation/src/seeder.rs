@@ -0,0 +1,221 @@
+use std::f32::consts::TAU;
+
+use rand::Rng;
+
+use super::{boundary::Boundary, grid::Grid};
+
+/// Pattern the field is filled with when the map is reset.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Seeder {
+    /// Uniform noise faded in along a different direction for every channel.
+    GradientNoise,
+    Uniform {
+        value: f32,
+    },
+    /// Independent uniform noise in `mean +- amplitude` for every cell.
+    WhiteNoise {
+        mean: f32,
+        amplitude: f32,
+    },
+    /// Perlin gradient noise with features of about `scale` cells, tiling
+    /// the grid seamlessly.
+    Perlin {
+        scale: f32,
+        octaves: u32,
+    },
+    /// Disks of `value` scattered over an empty field.
+    Spots {
+        count: usize,
+        radius: usize,
+        value: f32,
+    },
+    /// Sinusoidal stripes between 0 and 1.
+    Stripes {
+        wavelength: f32,
+        angle: f32,
+    },
+    /// Central disk of `value`, `radius` as a fraction of the half shorter side.
+    Disk {
+        radius: f32,
+        value: f32,
+    },
+    Checkerboard {
+        size: usize,
+    },
+    /// Keeps the current field and adds uniform noise of `amplitude`.
+    Perturb {
+        amplitude: f32,
+    },
+}
+
+impl Default for Seeder {
+    fn default() -> Self {
+        Self::GradientNoise
+    }
+}
+
+impl Seeder {
+    pub const PRESETS: [Self; 9] = [
+        Self::GradientNoise,
+        Self::Uniform { value: 0.5 },
+        Self::WhiteNoise {
+            mean: 0.5,
+            amplitude: 0.5,
+        },
+        Self::Perlin {
+            scale: 32.0,
+            octaves: 3,
+        },
+        Self::Spots {
+            count: 20,
+            radius: 5,
+            value: 1.0,
+        },
+        Self::Stripes {
+            wavelength: 16.0,
+            angle: 0.0,
+        },
+        Self::Disk {
+            radius: 0.2,
+            value: 1.0,
+        },
+        Self::Checkerboard { size: 16 },
+        Self::Perturb { amplitude: 0.05 },
+    ];
+
+    pub fn name(&self) -> &str {
+        match self {
+            Self::GradientNoise => "Gradient noise",
+            Self::Uniform { .. } => "Uniform",
+            Self::WhiteNoise { .. } => "White noise",
+            Self::Perlin { .. } => "Perlin noise",
+            Self::Spots { .. } => "Random spots",
+            Self::Stripes { .. } => "Stripes",
+            Self::Disk { .. } => "Central disk",
+            Self::Checkerboard { .. } => "Checkerboard",
+            Self::Perturb { .. } => "Perturb current",
+        }
+    }
+
+    /// Fills every channel of `grid`, drawing from `rng` where the pattern is
+    /// random. Spots crossing the edge follow `boundary`.
+    pub fn seed(&self, grid: &mut Grid, boundary: Boundary, rng: &mut impl Rng) {
+        let (width, height, channels) = (grid.width(), grid.height(), grid.channels());
+        match *self {
+            Self::GradientNoise => *grid = Grid::random(width, height, channels, rng),
+            Self::Uniform { value } => fill(grid, |_, _| value),
+            Self::WhiteNoise { mean, amplitude } => {
+                for channel in 0..channels {
+                    for value in grid.channel_mut(channel) {
+                        *value = mean + amplitude * rng.gen_range(-1.0..=1.0);
+                    }
+                }
+            }
+            Self::Perlin { scale, octaves } => {
+                for channel in 0..channels {
+                    perlin(grid, channel, scale, octaves, rng);
+                }
+            }
+            Self::Spots {
+                count,
+                radius,
+                value,
+            } => {
+                fill(grid, |_, _| 0.0);
+                let values = vec![value; channels];
+                for _ in 0..count {
+                    let x = rng.gen_range(0..width) as i32;
+                    let y = rng.gen_range(0..height) as i32;
+                    grid.paint(x, y, radius, &values, boundary);
+                }
+            }
+            Self::Stripes { wavelength, angle } => {
+                let (sin, cos) = angle.sin_cos();
+                fill(grid, |x, y| {
+                    let phase = (x as f32 * cos + y as f32 * sin) / wavelength;
+                    0.5 + 0.5 * (TAU * phase).cos()
+                });
+            }
+            Self::Disk { radius, value } => {
+                let radius = radius * 0.5 * width.min(height) as f32;
+                let (cx, cy) = (0.5 * width as f32, 0.5 * height as f32);
+                fill(grid, |x, y| {
+                    let (dx, dy) = (x as f32 + 0.5 - cx, y as f32 + 0.5 - cy);
+                    if dx * dx + dy * dy <= radius * radius {
+                        value
+                    } else {
+                        0.0
+                    }
+                });
+            }
+            Self::Checkerboard { size } => {
+                let size = size.max(1);
+                fill(grid, |x, y| ((x / size + y / size) % 2) as f32);
+            }
+            Self::Perturb { amplitude } => {
+                for channel in 0..channels {
+                    for value in grid.channel_mut(channel) {
+                        *value += amplitude * rng.gen_range(-1.0..=1.0);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Sets every channel of every cell to `value(x, y)`.
+fn fill(grid: &mut Grid, value: impl Fn(usize, usize) -> f32) {
+    let width = grid.width();
+    for channel in 0..grid.channels() {
+        for (index, cell) in grid.channel_mut(channel).iter_mut().enumerate() {
+            *cell = value(index % width, index / width);
+        }
+    }
+}
+
+// Sum of `octaves` layers of gradient noise, each twice as fine and half as
+// strong as the last, stretched to fill 0..1. The lattice of every layer
+// divides the grid evenly so the pattern wraps around the edges.
+fn perlin(grid: &mut Grid, channel: usize, scale: f32, octaves: u32, rng: &mut impl Rng) {
+    let (width, height) = (grid.width(), grid.height());
+    let mut noise = vec![0.0; width * height];
+    for octave in 0..octaves.max(1) {
+        let frequency = 1 << octave;
+        let amplitude = 0.5f32.powi(octave as i32);
+        let cells_x = ((width as f32 / scale).round() as usize).max(1) * frequency;
+        let cells_y = ((height as f32 / scale).round() as usize).max(1) * frequency;
+        let gradients: Vec<(f32, f32)> = (0..cells_x * cells_y)
+            .map(|_| {
+                let (sin, cos) = rng.gen_range(0.0..TAU).sin_cos();
+                (cos, sin)
+            })
+            .collect();
+        for (index, value) in noise.iter_mut().enumerate() {
+            let u = ((index % width) as f32 + 0.5) * cells_x as f32 / width as f32;
+            let v = ((index / width) as f32 + 0.5) * cells_y as f32 / height as f32;
+            let (x0, y0) = (u.floor() as usize, v.floor() as usize);
+            let (fx, fy) = (u.fract(), v.fract());
+            let corner = |dx: usize, dy: usize| {
+                let (gx, gy) = gradients[(y0 + dy) % cells_y * cells_x + (x0 + dx) % cells_x];
+                gx * (fx - dx as f32) + gy * (fy - dy as f32)
+            };
+            let (sx, sy) = (fade(fx), fade(fy));
+            let top = corner(0, 0) + sx * (corner(1, 0) - corner(0, 0));
+            let bottom = corner(0, 1) + sx * (corner(1, 1) - corner(0, 1));
+            *value += amplitude * (top + sy * (bottom - top));
+        }
+    }
+    let (low, high) = noise
+        .iter()
+        .fold((f32::MAX, f32::MIN), |(low, high), value| {
+            (low.min(*value), high.max(*value))
+        });
+    let range = (high - low).max(f32::EPSILON);
+    for (cell, value) in grid.channel_mut(channel).iter_mut().zip(noise) {
+        *cell = (value - low) / range;
+    }
+}
+
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
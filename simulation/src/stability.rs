@@ -0,0 +1,73 @@
+use std::fmt;
+
+use super::grid::Grid;
+
+/// Magnitude beyond which a value counts as blown up. Concentrations are
+/// normalized to about 0 to 1, so only a diverging run gets this far.
+pub const SATURATION_LIMIT: f32 = 1e3;
+
+/// First blown up cell found in a grid.
+#[derive(Clone, Debug)]
+pub enum Instability {
+    NotFinite {
+        channel: usize,
+        x: usize,
+        y: usize,
+    },
+    Saturated {
+        channel: usize,
+        x: usize,
+        y: usize,
+        value: f32,
+    },
+}
+
+impl fmt::Display for Instability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFinite { channel, x, y } => write!(
+                f,
+                "channel {} became NaN or infinite at ({}, {})",
+                channel + 1,
+                x,
+                y
+            ),
+            Self::Saturated {
+                channel,
+                x,
+                y,
+                value,
+            } => write!(
+                f,
+                "channel {} reached {:.3e} at ({}, {})",
+                channel + 1,
+                value,
+                x,
+                y
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Instability {}
+
+pub fn check(grid: &Grid) -> Result<(), Instability> {
+    let width = grid.width();
+    for channel in 0..grid.channels() {
+        for (index, value) in grid.channel(channel).iter().enumerate() {
+            let (x, y) = (index % width, index / width);
+            if !value.is_finite() {
+                return Err(Instability::NotFinite { channel, x, y });
+            }
+            if value.abs() > SATURATION_LIMIT {
+                return Err(Instability::Saturated {
+                    channel,
+                    x,
+                    y,
+                    value: *value,
+                });
+            }
+        }
+    }
+    Ok(())
+}
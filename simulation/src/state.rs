@@ -0,0 +1,641 @@
+use std::sync::Arc;
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use super::{
+    advection::{self, Advection, AdvectionScheme, Flow},
+    boundary::{Boundary, Neighbour},
+    channel::ChannelParameters,
+    chemotaxis::Chemotaxis,
+    domain::{CellKind, Domain},
+    grid::Grid,
+    integrator::{self, Integrator, Workspace},
+    lenia::Lenia,
+    life::{LifeRule, LifeWorkspace},
+    noise::NoiseField,
+    parallel,
+    parameter_map::{ParameterMap, ParameterTarget},
+    reaction::{CyclicHolling, ReactionModel},
+    seeder::Seeder,
+    stability::{self, Instability},
+    stencil::{Stencil, StencilCache},
+};
+
+#[derive(Clone)]
+pub enum Automaton {
+    ReactionDiffusion,
+    Lenia(Lenia),
+    Life(LifeRule),
+}
+
+impl Automaton {
+    pub fn name(&self) -> &str {
+        match self {
+            Self::ReactionDiffusion => "Reaction-Diffusion",
+            Self::Lenia(_) => "Lenia",
+            Self::Life(_) => "Life-like",
+        }
+    }
+}
+
+// Independent random streams derived from the seed, so drawing random rules
+// does not shift the initial field or the noise.
+const FIELD_STREAM: u64 = 0;
+const RULES_STREAM: u64 = 1;
+
+#[derive(Clone)]
+pub struct Simulation {
+    pub grid: Grid,
+    model: Arc<dyn ReactionModel>,
+    pub parameters: Vec<f32>,
+    pub channels: Vec<ChannelParameters>,
+    /// Parameters that vary over the grid, overriding their global value.
+    pub parameter_maps: Vec<ParameterMap>,
+    pub stencil: Stencil,
+    pub boundary: Boundary,
+    pub integrator: Integrator,
+    /// Time advanced by one reaction-diffusion step.
+    pub dt: f32,
+    /// Split steps longer than the stability limit into stable sub-steps.
+    pub auto_substep: bool,
+    /// Flow transporting the reaction-diffusion channels.
+    pub advection: Advection,
+    /// Walls and fixed cells of the reaction-diffusion domain.
+    pub domain: Domain,
+    pub chemotaxis: Option<Chemotaxis>,
+    /// Pattern `reset` fills the field with.
+    pub seeder: Seeder,
+    automaton: Automaton,
+    workspace: Workspace,
+    band_buffers: Vec<BandBuffers>,
+    stencil_cache: StencilCache,
+    life_workspace: LifeWorkspace,
+    substeps: usize,
+    seed: u64,
+    rng: ChaCha8Rng,
+    rules_rng: ChaCha8Rng,
+    noise_field: NoiseField,
+    map_values: Vec<Vec<f32>>,
+    traced: Grid,
+    // Attractant channel and steepest attractant the last step met, bounding
+    // the chemotaxis rate of the next.
+    attractant_steepness: Option<(usize, f32)>,
+}
+
+impl Simulation {
+    pub fn new(width: usize, height: usize, model: Arc<dyn ReactionModel>) -> Self {
+        let channels = model.channel_count().unwrap_or(3);
+        let seed = Self::random_seed();
+        let mut rng = seeded_rng(seed, FIELD_STREAM);
+        let mut simulation = Self {
+            grid: Grid::random(width, height, channels, &mut rng),
+            model,
+            parameters: Vec::new(),
+            channels: Vec::new(),
+            parameter_maps: Vec::new(),
+            stencil: Stencil::default(),
+            boundary: Boundary::default(),
+            integrator: Integrator::default(),
+            dt: 1.0,
+            auto_substep: false,
+            advection: Advection::default(),
+            domain: Domain::default(),
+            chemotaxis: None,
+            seeder: Seeder::default(),
+            automaton: Automaton::ReactionDiffusion,
+            workspace: Workspace::default(),
+            band_buffers: Vec::new(),
+            stencil_cache: StencilCache::default(),
+            life_workspace: LifeWorkspace::default(),
+            substeps: 0,
+            seed,
+            rng,
+            rules_rng: seeded_rng(seed, RULES_STREAM),
+            noise_field: NoiseField::default(),
+            map_values: Vec::new(),
+            traced: Grid::default(),
+            attractant_steepness: None,
+        };
+        simulation.reset_rules();
+        simulation
+    }
+
+    /// Fresh seed from the operating system.
+    pub fn random_seed() -> u64 {
+        rand::thread_rng().gen::<u32>().into()
+    }
+
+    pub(crate) fn rng(&self) -> &ChaCha8Rng {
+        &self.rng
+    }
+
+    pub(crate) fn set_rng(&mut self, rng: ChaCha8Rng) {
+        self.rng = rng;
+    }
+
+    // Puts back a field recorded earlier.
+    pub(crate) fn set_grid(&mut self, grid: Grid) {
+        self.grid = grid;
+        self.attractant_steepness = None;
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Restarts every random stream from `seed`: the initial field drawn by
+    /// the next `reset`, the noise after it and the random rules.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = seeded_rng(seed, FIELD_STREAM);
+        self.rules_rng = seeded_rng(seed, RULES_STREAM);
+    }
+
+    pub fn model(&self) -> &Arc<dyn ReactionModel> {
+        &self.model
+    }
+
+    pub fn set_model(&mut self, model: Arc<dyn ReactionModel>) {
+        self.model = model;
+        self.parameter_maps.clear();
+        if let Some(channels) = self.fixed_channel_count() {
+            self.grid.set_channel_count(channels, &mut self.rng);
+        }
+        self.reset_rules();
+    }
+
+    pub fn automaton(&self) -> &Automaton {
+        &self.automaton
+    }
+
+    pub fn automaton_mut(&mut self) -> &mut Automaton {
+        &mut self.automaton
+    }
+
+    pub fn set_automaton(&mut self, automaton: Automaton) {
+        self.automaton = automaton;
+        if let Some(channels) = self.fixed_channel_count() {
+            if channels != self.channel_count() {
+                self.grid.set_channel_count(channels, &mut self.rng);
+                self.reset_rules();
+            }
+        }
+    }
+
+    /// Channel count imposed by the current automaton, if any.
+    pub fn fixed_channel_count(&self) -> Option<usize> {
+        match self.automaton {
+            Automaton::ReactionDiffusion => self.model.channel_count(),
+            Automaton::Lenia(_) => None,
+            Automaton::Life(_) => Some(2),
+        }
+    }
+
+    pub fn channel_name(&self, channel: usize) -> Option<String> {
+        match self.automaton {
+            Automaton::ReactionDiffusion => self.model.channel_name(channel),
+            Automaton::Lenia(_) => None,
+            Automaton::Life(_) => ["State", "Age"].get(channel).map(|name| (*name).to_owned()),
+        }
+    }
+
+    pub fn channel_count(&self) -> usize {
+        self.grid.channels()
+    }
+
+    pub fn set_channel_count(&mut self, channels: usize) {
+        if self.fixed_channel_count().is_some() {
+            return;
+        }
+        let model = self.model.clone();
+        self.channels.truncate(channels);
+        for channel in self.channels.len()..channels {
+            self.channels.push(ChannelParameters::from_specs(
+                &model.diffusion(channel),
+                &model.channel_parameters(channel),
+            ));
+        }
+        self.grid.set_channel_count(channels, &mut self.rng);
+    }
+
+    /// Changes the resolution, resampling the current field.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        self.grid = self.grid.resampled(width.max(2), height.max(2));
+        self.attractant_steepness = None;
+        for map in self.parameter_maps.iter_mut() {
+            map.resize(self.grid.width(), self.grid.height());
+        }
+        if !self.domain.is_open() {
+            self.domain
+                .fit(self.grid.width(), self.grid.height(), self.grid.channels());
+        }
+        if let Automaton::Life(rule) = &self.automaton {
+            rule.quantize(&mut self.grid);
+        }
+    }
+
+    /// Sets the cells within `radius` of `(x, y)` to `values`.
+    pub fn paint(&mut self, x: i32, y: i32, radius: usize, values: &[f32]) {
+        self.grid.paint(x, y, radius, values, self.boundary);
+        self.attractant_steepness = None;
+    }
+
+    /// Marks the cells within `radius` of `(x, y)` as `kind`, fixed cells
+    /// holding `values`, and applies the mask to the field at once.
+    pub fn paint_domain(&mut self, x: i32, y: i32, radius: usize, kind: CellKind, values: &[f32]) {
+        let (width, height) = (self.grid.width(), self.grid.height());
+        self.domain.fit(width, height, self.grid.channels());
+        self.domain.paint(x, y, radius, kind, values, self.boundary);
+        self.domain.enforce(&mut self.grid);
+        self.attractant_steepness = None;
+    }
+
+    /// Stirs the fluid flow, if any, adding `impulse` to the velocity within
+    /// `radius` cells of `(x, y)`.
+    pub fn add_momentum(&mut self, x: i32, y: i32, radius: usize, impulse: [f32; 2]) {
+        if let Flow::Fluid(fluid) = &mut self.advection.flow {
+            fluid.fit(self.grid.width(), self.grid.height());
+            fluid.add_momentum(x, y, radius, impulse);
+        }
+    }
+
+    pub fn reset(&mut self) {
+        if let Flow::Fluid(fluid) = &mut self.advection.flow {
+            fluid.clear();
+        }
+        self.rng = seeded_rng(self.seed, FIELD_STREAM);
+        self.attractant_steepness = None;
+        self.seeder
+            .seed(&mut self.grid, self.boundary, &mut self.rng);
+        if let Automaton::Life(rule) = &self.automaton {
+            if self.seeder == Seeder::GradientNoise {
+                LifeRule::randomize(&mut self.grid, 0.3, &mut self.rng);
+            } else {
+                // Every seeded cell starts with age zero.
+                rule.quantize(&mut self.grid);
+                for channel in 1..self.grid.channels() {
+                    self.grid.channel_mut(channel).fill(0.0);
+                }
+            }
+        }
+        if matches!(self.automaton, Automaton::ReactionDiffusion) && !self.domain.is_open() {
+            self.domain.enforce(&mut self.grid);
+        }
+    }
+
+    pub fn reset_rules(&mut self) {
+        self.parameters = self
+            .model
+            .parameters()
+            .iter()
+            .map(|spec| spec.default)
+            .collect();
+        self.channels = (0..self.grid.channels())
+            .map(|channel| {
+                ChannelParameters::from_specs(
+                    &self.model.diffusion(channel),
+                    &self.model.channel_parameters(channel),
+                )
+            })
+            .collect();
+    }
+
+    pub fn randomize_rules(&mut self) {
+        self.parameters = self
+            .model
+            .parameters()
+            .iter()
+            .map(|spec| spec.sample(&mut self.rules_rng))
+            .collect();
+        for (channel, parameters) in self.channels.iter_mut().enumerate() {
+            *parameters = ChannelParameters {
+                cross_diffusion: std::mem::take(&mut parameters.cross_diffusion),
+                noise: parameters.noise,
+                ..ChannelParameters::random(
+                    &self.model.diffusion(channel),
+                    &self.model.channel_parameters(channel),
+                    &mut self.rules_rng,
+                )
+            };
+        }
+    }
+
+    pub fn step(&mut self) {
+        match &mut self.automaton {
+            Automaton::ReactionDiffusion => self.reaction_diffusion_step(),
+            Automaton::Lenia(lenia) => lenia.step(&mut self.grid, self.boundary),
+            Automaton::Life(rule) => {
+                rule.step(&mut self.grid, self.boundary, &mut self.life_workspace)
+            }
+        }
+    }
+
+    /// Number of integrator steps the last reaction-diffusion step took.
+    pub fn substeps(&self) -> usize {
+        self.substeps
+    }
+
+    /// Largest `dt` for which the explicit diffusion update with the current
+    /// stencil, integrator and diffusion coefficients stays stable, `None`
+    /// when nothing diffuses. The spectral radius of the stencil is cached
+    /// until the stencil changes, the rest is a few products per channel.
+    pub fn stable_time_step(&mut self) -> Option<f32> {
+        self.stencil_cache.fit(&self.stencil);
+        let diffusion = self
+            .channels
+            .iter()
+            .enumerate()
+            .map(|(index, channel)| {
+                let own = self
+                    .parameter_maps
+                    .iter()
+                    .filter(|map| map.target == ParameterTarget::Diffusion(index))
+                    .map(ParameterMap::max_abs)
+                    .fold(channel.diffusion_coefficient.abs(), f32::max);
+                // Gershgorin: the cross terms widen the disk the eigenvalues
+                // of the diffusion matrix lie in.
+                let cross: f32 = (0..self.channels.len())
+                    .filter(|source| *source != index)
+                    .map(|source| channel.diffusion_from(index, source).abs())
+                    .sum();
+                own + cross
+            })
+            .fold(0.0, f32::max);
+        // The upwind operator has its eigenvalues in a disk of radius
+        // |vx| + |vy| centred on -(|vx| + |vy|).
+        let advection = match self.advection.scheme {
+            AdvectionScheme::Upwind => 2.0 * self.advection.flow.max_speed(),
+            AdvectionScheme::SemiLagrangian => 0.0,
+        };
+        // Chemotaxis moves cells like the upwind scheme, at a speed set by
+        // the attractant gradient.
+        let chemotaxis = self.active_chemotaxis().map_or(0.0, |chemotaxis| {
+            let steepness = match self.attractant_steepness {
+                Some((attractant, steepness)) if attractant == chemotaxis.attractant => steepness,
+                _ => self.attractant_steepness(&chemotaxis),
+            };
+            2.0 * chemotaxis.max_rate(steepness)
+        });
+        let rate = diffusion * self.stencil_cache.spectral_radius + advection + chemotaxis;
+        (rate > 0.0).then(|| self.integrator.stability_radius() / rate)
+    }
+
+    fn active_chemotaxis(&self) -> Option<Chemotaxis> {
+        self.chemotaxis
+            .filter(|chemotaxis| chemotaxis.fits(self.grid.channels()))
+    }
+
+    // Steepest attractant over the open cells, for when no step measured it
+    // yet.
+    fn attractant_steepness(&self, chemotaxis: &Chemotaxis) -> f32 {
+        let grid = &self.grid;
+        let domain = (!self.domain.is_open()).then_some(&self.domain);
+        let mut steepest = 0.0f32;
+        for y in 0..grid.height() {
+            for x in 0..grid.width() {
+                if domain.is_some_and(|domain| domain.kind(x, y) != CellKind::Open) {
+                    continue;
+                }
+                let read = |channel, dx, dy| {
+                    neighbour_value(grid, self.boundary, domain, (x, y), (dx, dy), channel)
+                };
+                let attractant = grid.get(x, y, chemotaxis.attractant);
+                steepest = steepest.max(chemotaxis.steepness(attractant, read));
+            }
+        }
+        steepest
+    }
+
+    /// Looks for NaN, infinite or saturated values left by a diverging run.
+    /// Only reaction-diffusion can diverge: Lenia clips its field and the
+    /// age of Life cells grows without bound by design.
+    pub fn check(&self) -> Result<(), Instability> {
+        match self.automaton {
+            Automaton::ReactionDiffusion => stability::check(&self.grid),
+            Automaton::Lenia(_) | Automaton::Life(_) => Ok(()),
+        }
+    }
+
+    fn reaction_diffusion_step(&mut self) {
+        let (width, height) = (self.grid.width(), self.grid.height());
+        self.map_values
+            .resize_with(self.parameter_maps.len(), Vec::new);
+        for (map, values) in self.parameter_maps.iter().zip(self.map_values.iter_mut()) {
+            map.bake(width, height, values);
+        }
+        self.domain.fit(width, height, self.grid.channels());
+        let walls = (!self.domain.is_open()).then_some(&self.domain);
+        self.advection
+            .update_velocity(width, height, self.dt, walls);
+        self.stencil_cache.fit(&self.stencil);
+        // Without auto-substep the limit only feeds the warning of the app.
+        let limit = self.auto_substep.then(|| self.stable_time_step()).flatten();
+        let steps = match limit {
+            Some(limit) if self.dt > limit => (self.dt / limit).ceil() as usize,
+            _ => 1,
+        };
+        let domain = (!self.domain.is_open()).then_some(&self.domain);
+        let rates = ReactionDiffusionRates {
+            model: &*self.model,
+            parameters: &self.parameters,
+            channels: &self.channels,
+            parameter_maps: &self.parameter_maps,
+            map_values: &self.map_values,
+            taps: &self.stencil_cache.taps,
+            self_weight: self.stencil_cache.self_weight,
+            boundary: self.boundary,
+            velocity: self.advection.upwind_velocity(),
+            domain,
+            chemotaxis: self.active_chemotaxis(),
+        };
+        let dt = self.dt / steps as f32;
+        self.substeps = 0;
+        for buffers in self.band_buffers.iter_mut() {
+            buffers.steepness = 0.0;
+        }
+        for _ in 0..steps {
+            self.substeps += integrator::integrate(
+                self.integrator,
+                &mut self.grid,
+                dt,
+                &mut self.workspace,
+                |state, derivative| rates.evaluate(state, derivative, &mut self.band_buffers),
+            );
+            self.advection
+                .transport(&mut self.grid, &mut self.traced, dt, self.boundary, domain);
+            for (channel, parameters) in self.channels.iter().enumerate() {
+                if parameters.noise.is_active() && channel < self.grid.channels() {
+                    self.noise_field.apply(
+                        &mut self.grid,
+                        channel,
+                        &parameters.noise,
+                        dt,
+                        &mut self.rng,
+                    );
+                }
+            }
+            if self.model.non_negative() {
+                self.grid.clamp_non_negative();
+            }
+            if let Some(domain) = domain {
+                domain.enforce(&mut self.grid);
+            }
+        }
+        self.attractant_steepness = rates.chemotaxis.map(|chemotaxis| {
+            let steepness = self.band_buffers.iter().map(|buffers| buffers.steepness);
+            (chemotaxis.attractant, steepness.fold(0.0, f32::max))
+        });
+    }
+}
+
+// Right hand side of the reaction-diffusion equations.
+struct ReactionDiffusionRates<'a> {
+    model: &'a dyn ReactionModel,
+    parameters: &'a [f32],
+    channels: &'a [ChannelParameters],
+    parameter_maps: &'a [ParameterMap],
+    map_values: &'a [Vec<f32>],
+    taps: &'a [(i32, i32, f32)],
+    self_weight: f32,
+    boundary: Boundary,
+    velocity: Option<&'a [[f32; 2]]>,
+    domain: Option<&'a Domain>,
+    chemotaxis: Option<Chemotaxis>,
+}
+
+// Buffers of one band of the rate evaluation, kept between steps.
+#[derive(Clone, Default)]
+struct BandBuffers {
+    cell: Vec<f32>,
+    rates: Vec<f32>,
+    laplacians: Vec<f32>,
+    // Local copies, overwritten cell by cell where a parameter is mapped.
+    parameters: Vec<f32>,
+    channel_parameters: Vec<ChannelParameters>,
+    // Steepest attractant of the band over the evaluations of a step.
+    steepness: f32,
+}
+
+impl ReactionDiffusionRates<'_> {
+    fn evaluate(&self, grid: &Grid, derivative: &mut Grid, buffers: &mut Vec<BandBuffers>) {
+        let (width, height, channels) = (grid.width(), grid.height(), grid.channels());
+        let rows = parallel::band_rows(height);
+        buffers.resize_with((height + rows - 1) / rows, BandBuffers::default);
+        let bands = derivative.bands_mut(rows).zip(buffers.iter_mut());
+        parallel::for_each_band(bands, |(mut band, buffers)| {
+            let BandBuffers {
+                cell,
+                rates,
+                laplacians,
+                parameters,
+                channel_parameters,
+                steepness,
+            } = buffers;
+            for buffer in [&mut *cell, &mut *rates, &mut *laplacians] {
+                buffer.resize(channels, 0.0);
+            }
+            self.parameters.clone_into(parameters);
+            self.channels.clone_into(channel_parameters);
+            for row in 0..band.rows {
+                let y = band.first_row + row;
+                for x in 0..width {
+                    // Walls and fixed cells hold still.
+                    if self
+                        .domain
+                        .is_some_and(|domain| domain.kind(x, y) != CellKind::Open)
+                    {
+                        for output in band.channels.iter_mut() {
+                            output[row * width + x] = 0.0;
+                        }
+                        continue;
+                    }
+                    for (channel, value) in cell.iter_mut().enumerate() {
+                        *value = grid.get(x, y, channel);
+                    }
+                    for (map, values) in self.parameter_maps.iter().zip(self.map_values) {
+                        if let Some(value) = map.target.value_mut(parameters, channel_parameters) {
+                            *value = values[y * width + x];
+                        }
+                    }
+                    self.model
+                        .react(cell, parameters, channel_parameters, rates);
+                    let read = |channel, dx, dy| {
+                        neighbour_value(grid, self.boundary, self.domain, (x, y), (dx, dy), channel)
+                    };
+                    for channel in 0..channel_parameters.len() {
+                        let neighbour = |dx, dy| read(channel, dx, dy);
+                        let sum_neighbours: f32 = self
+                            .taps
+                            .iter()
+                            .map(|(dx, dy, weight)| neighbour(*dx, *dy) * weight)
+                            .sum();
+                        laplacians[channel] = sum_neighbours - self.self_weight * cell[channel];
+                        let advection = self.velocity.map_or(0.0, |velocity| {
+                            let own = velocity[y * width + x];
+                            advection::upwind(cell[channel], own, |dx, dy| {
+                                if self.domain.is_some_and(|domain| {
+                                    domain.blocked(self.boundary, (x, y), (dx, dy))
+                                }) {
+                                    return None;
+                                }
+                                let other = match self.boundary.neighbour(
+                                    x as i32 + dx,
+                                    y as i32 + dy,
+                                    width,
+                                    height,
+                                ) {
+                                    Neighbour::Cell(nx, ny) => velocity[ny * width + nx],
+                                    Neighbour::Value(_) => own,
+                                };
+                                Some((neighbour(dx, dy), other))
+                            })
+                        });
+                        band.channels[channel][row * width + x] = advection + rates[channel];
+                    }
+                    for (channel, params) in channel_parameters.iter().enumerate() {
+                        band.channels[channel][row * width + x] +=
+                            params.diffusion(channel, laplacians);
+                    }
+                    if let Some(chemotaxis) = self.chemotaxis {
+                        let attractant = cell[chemotaxis.attractant];
+                        *steepness = steepness.max(chemotaxis.steepness(attractant, read));
+                        band.channels[chemotaxis.cells][row * width + x] += chemotaxis.rate(
+                            cell[chemotaxis.cells],
+                            cell[chemotaxis.attractant],
+                            read,
+                        );
+                    }
+                }
+            }
+        });
+    }
+}
+
+impl Default for Simulation {
+    fn default() -> Self {
+        Self::new(160, 160, Arc::new(CyclicHolling))
+    }
+}
+
+// Value the stencil of cell `(x, y)` reads at offset `(dx, dy)`, through the
+// walls of `domain` if any.
+fn neighbour_value(
+    grid: &Grid,
+    boundary: Boundary,
+    domain: Option<&Domain>,
+    (x, y): (usize, usize),
+    (dx, dy): (i32, i32),
+    channel: usize,
+) -> f32 {
+    match domain {
+        Some(domain) => domain.neighbour(grid, boundary, (x, y), (dx, dy), channel),
+        None => boundary.value(grid, x as i32 + dx, y as i32 + dy, channel),
+    }
+}
+
+fn seeded_rng(seed: u64, stream: u64) -> ChaCha8Rng {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    rng.set_stream(stream);
+    rng
+}
@@ -0,0 +1,206 @@
+/// Discrete Laplacian used by the diffusion term.
+///
+/// Only the neighbour weights are given; the weight of the cell itself is
+/// always their negated sum, so every stencil conserves the total mass.
+#[derive(Clone, PartialEq)]
+pub enum Stencil {
+    Classic,
+    FivePoint,
+    NinePointIsotropic,
+    Custom(Kernel),
+}
+
+/// Square `size` x `size` kernel of neighbour weights, row by row. The
+/// centre entry is ignored.
+#[derive(Clone, PartialEq)]
+pub struct Kernel {
+    size: usize,
+    pub weights: Vec<f32>,
+}
+
+impl Kernel {
+    pub fn new(size: usize) -> Self {
+        let size = size | 1;
+        Self {
+            size,
+            weights: vec![0.0; size * size],
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn radius(&self) -> i32 {
+        (self.size / 2) as i32
+    }
+
+    pub fn weight(&self, dx: i32, dy: i32) -> f32 {
+        let radius = self.radius();
+        if dx.abs() > radius || dy.abs() > radius {
+            return 0.0;
+        }
+        self.weights[((dy + radius) as usize) * self.size + (dx + radius) as usize]
+    }
+
+    pub fn set_weight(&mut self, dx: i32, dy: i32, weight: f32) {
+        let radius = self.radius();
+        self.weights[((dy + radius) as usize) * self.size + (dx + radius) as usize] = weight;
+    }
+}
+
+impl Stencil {
+    pub const PRESETS: [Stencil; 3] = [
+        Stencil::Classic,
+        Stencil::FivePoint,
+        Stencil::NinePointIsotropic,
+    ];
+
+    pub fn name(&self) -> String {
+        match self {
+            Self::Classic => "Classic 9-point".to_owned(),
+            Self::FivePoint => "5-point".to_owned(),
+            Self::NinePointIsotropic => "Isotropic 9-point".to_owned(),
+            Self::Custom(kernel) => format!("Custom {0}x{0}", kernel.size()),
+        }
+    }
+
+    /// Neighbour offsets with a non-zero weight, as `(dx, dy, weight)`.
+    pub fn taps(&self) -> Vec<(i32, i32, f32)> {
+        match self {
+            Self::Classic => ring_taps(1.0, 1.0 / 1.41),
+            Self::FivePoint => ring_taps(1.0, 0.0),
+            Self::NinePointIsotropic => ring_taps(2.0 / 3.0, 1.0 / 6.0),
+            Self::Custom(kernel) => {
+                let radius = kernel.radius();
+                let mut taps = Vec::new();
+                for dy in -radius..=radius {
+                    for dx in -radius..=radius {
+                        let weight = kernel.weight(dx, dy);
+                        if (dx, dy) != (0, 0) && weight != 0.0 {
+                            taps.push((dx, dy, weight));
+                        }
+                    }
+                }
+                taps
+            }
+        }
+    }
+
+    pub fn self_weight(&self) -> f32 {
+        self.taps().iter().map(|(_, _, weight)| weight).sum()
+    }
+
+    /// Largest modulus of the eigenvalues of the discrete Laplacian over all
+    /// wave numbers, the one that limits the time step of explicit schemes.
+    /// Asymmetric custom kernels have complex eigenvalues, so the sine part
+    /// of the symbol counts as well.
+    pub fn spectral_radius(&self) -> f32 {
+        const SAMPLES: usize = 64;
+        let taps = self.taps();
+        let self_weight = self.self_weight();
+        let mut radius = 0.0f32;
+        // The symbol at -k is the conjugate of the one at k, so half of the
+        // Brillouin zone suffices.
+        for i in 0..=SAMPLES {
+            let kx = std::f32::consts::PI * i as f32 / SAMPLES as f32;
+            for j in 0..=2 * SAMPLES {
+                let ky = std::f32::consts::PI * (j as f32 / SAMPLES as f32 - 1.0);
+                let (mut real, mut imaginary) = (-self_weight, 0.0f32);
+                for (dx, dy, weight) in &taps {
+                    let (sin, cos) = (kx * *dx as f32 + ky * *dy as f32).sin_cos();
+                    real += weight * cos;
+                    imaginary += weight * sin;
+                }
+                radius = radius.max(real.hypot(imaginary));
+            }
+        }
+        radius
+    }
+
+    /// Copies the weights of this stencil into an editable kernel.
+    pub fn to_kernel(&self, size: usize) -> Kernel {
+        let mut kernel = Kernel::new(size);
+        let radius = kernel.radius();
+        for (dx, dy, weight) in self.taps() {
+            if dx.abs() <= radius && dy.abs() <= radius {
+                kernel.set_weight(dx, dy, weight);
+            }
+        }
+        kernel
+    }
+}
+
+/// Taps and derived constants of a stencil, recomputed only when it changes.
+#[derive(Clone, Default)]
+pub(crate) struct StencilCache {
+    stencil: Option<Stencil>,
+    pub taps: Vec<(i32, i32, f32)>,
+    pub self_weight: f32,
+    pub spectral_radius: f32,
+}
+
+impl StencilCache {
+    pub fn fit(&mut self, stencil: &Stencil) {
+        if self.stencil.as_ref() != Some(stencil) {
+            self.taps = stencil.taps();
+            self.self_weight = stencil.self_weight();
+            self.spectral_radius = stencil.spectral_radius();
+            self.stencil = Some(stencil.clone());
+        }
+    }
+}
+
+impl Default for Stencil {
+    fn default() -> Self {
+        Self::Classic
+    }
+}
+
+fn ring_taps(orthogonal: f32, diagonal: f32) -> Vec<(i32, i32, f32)> {
+    let mut taps = vec![
+        (0, -1, orthogonal),
+        (1, 0, orthogonal),
+        (0, 1, orthogonal),
+        (-1, 0, orthogonal),
+    ];
+    if diagonal != 0.0 {
+        taps.extend([
+            (-1, -1, diagonal),
+            (1, -1, diagonal),
+            (1, 1, diagonal),
+            (-1, 1, diagonal),
+        ]);
+    }
+    taps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spectral_radius_of_the_five_point_stencil() {
+        assert!((Stencil::FivePoint.spectral_radius() - 8.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn spectral_radius_bounds_asymmetric_kernels() {
+        // One-sided taps to the right: the eigenvalue e^{ik} + e^{2ik} - 2
+        // peaks off the real axis, above what its real part reaches.
+        let mut kernel = Kernel::new(5);
+        kernel.set_weight(1, 0, 1.0);
+        kernel.set_weight(2, 0, 1.0);
+        let radius = Stencil::Custom(kernel).spectral_radius();
+        let peak = (0..=10_000)
+            .map(|i| {
+                let k = std::f32::consts::PI * i as f32 / 10_000.0;
+                let real = k.cos() + (2.0 * k).cos() - 2.0;
+                let imaginary = k.sin() + (2.0 * k).sin();
+                real.hypot(imaginary)
+            })
+            .fold(0.0f32, f32::max);
+        assert!(radius >= peak - 1e-3, "{radius} < {peak}");
+        assert!(radius <= peak + 1e-3, "{radius} > {peak}");
+    }
+}
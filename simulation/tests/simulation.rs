@@ -0,0 +1,238 @@
+use std::sync::Arc;
+
+use simulation::{
+    reaction::{self, CustomModel, GrayScott},
+    AdvectionScheme, Automaton, Boundary, CellKind, Chemotaxis, Flow, Kernel, Lenia, LifeRule,
+    Seeder, Simulation, Stencil,
+};
+
+// Reaction-free model whose channels only diffuse.
+fn pure_diffusion(channels: usize) -> Simulation {
+    let sources = vec!["0".to_owned(); channels];
+    let model = CustomModel::compile(&sources).unwrap();
+    let mut simulation = Simulation::new(32, 24, Arc::new(model));
+    simulation.set_seed(7);
+    simulation.reset();
+    simulation
+}
+
+fn total(simulation: &Simulation, channel: usize) -> f64 {
+    simulation
+        .grid
+        .channel(channel)
+        .iter()
+        .map(|value| *value as f64)
+        .sum()
+}
+
+#[test]
+fn gray_scott_step_matches_hand_computation() {
+    let mut simulation = Simulation::new(8, 8, Arc::new(GrayScott));
+    for y in 0..8 {
+        for x in 0..8 {
+            simulation.grid.set(x, y, 0, 1.0);
+            simulation.grid.set(x, y, 1, 0.0);
+        }
+    }
+    simulation.grid.set(4, 4, 0, 0.5);
+    simulation.grid.set(4, 4, 1, 0.25);
+    simulation.step();
+
+    // Classic 9-point stencil, Euler with dt = 1, feed 0.055, kill 0.062.
+    let self_weight = 4.0 + 4.0 / 1.41;
+    let (u, v) = (0.5f32, 0.25f32);
+    let laplacian_u = self_weight * 1.0 - self_weight * u;
+    let laplacian_v = -self_weight * v;
+    let expected_u = u + 0.124 * laplacian_u - u * v * v + 0.055 * (1.0 - u);
+    let expected_v = v + 0.062 * laplacian_v + u * v * v - (0.055 + 0.062) * v;
+    assert!((simulation.grid.get(4, 4, 0) - expected_u).abs() < 1e-6);
+    assert!((simulation.grid.get(4, 4, 1) - expected_v).abs() < 1e-6);
+    // A plain neighbour only receives from the centre by diffusion.
+    let expected_neighbour = 0.062 * 0.25;
+    assert!((simulation.grid.get(5, 4, 1) - expected_neighbour).abs() < 1e-6);
+}
+
+#[test]
+fn diffusion_conserves_mass() {
+    for boundary in [Boundary::Periodic, Boundary::Neumann] {
+        let mut simulation = pure_diffusion(1);
+        simulation.boundary = boundary;
+        simulation.channels[0].diffusion_coefficient = 0.1;
+        let before = total(&simulation, 0);
+        for _ in 0..50 {
+            simulation.step();
+        }
+        let after = total(&simulation, 0);
+        assert!(
+            (after - before).abs() < 1e-4 * before,
+            "{}: {before} -> {after}",
+            boundary.name()
+        );
+    }
+}
+
+#[test]
+fn walls_conserve_mass_under_transport() {
+    let flows = [
+        Flow::Uniform {
+            velocity: [0.9, 0.4],
+        },
+        Flow::Vortex {
+            angular_speed: 0.1,
+            radius: 12.0,
+        },
+        Flow::Fluid(Box::default()),
+    ];
+    for scheme in AdvectionScheme::PRESETS {
+        for flow in flows.clone() {
+            let mut simulation = pure_diffusion(1);
+            simulation.channels[0].diffusion_coefficient = 0.05;
+            simulation.auto_substep = true;
+            simulation.advection.scheme = scheme;
+            simulation.advection.flow = flow;
+            // A block and a one cell thick wall across the stream.
+            simulation.paint_domain(10, 12, 4, CellKind::Wall, &[]);
+            for y in 0..20 {
+                simulation.paint_domain(24, y, 0, CellKind::Wall, &[]);
+            }
+            simulation.add_momentum(16, 12, 6, [4.0, 1.0]);
+            let before = total(&simulation, 0);
+            for _ in 0..50 {
+                simulation.step();
+            }
+            let after = total(&simulation, 0);
+            assert!(
+                (after - before).abs() < 1e-4 * before,
+                "{} in {}: {before} -> {after}",
+                scheme.name(),
+                simulation.advection.flow.name()
+            );
+        }
+    }
+}
+
+#[test]
+fn walls_hold_back_wide_stencils() {
+    let mut kernel = Kernel::new(5);
+    kernel.weights.fill(0.05);
+    let mut simulation = pure_diffusion(1);
+    simulation.stencil = Stencil::Custom(kernel);
+    simulation.boundary = Boundary::Neumann;
+    simulation.channels[0].diffusion_coefficient = 0.1;
+    for y in 0..24 {
+        for x in 0..32 {
+            simulation.grid.set(x, y, 0, if x < 12 { 1.0 } else { 0.0 });
+        }
+        simulation.paint_domain(12, y as i32, 0, CellKind::Wall, &[]);
+    }
+    for _ in 0..50 {
+        simulation.step();
+    }
+    for y in 0..24 {
+        for x in 13..32 {
+            assert_eq!(simulation.grid.get(x, y, 0), 0.0, "leak at ({x}, {y})");
+        }
+    }
+}
+
+#[test]
+fn builtin_models_run_stably_from_default_seeders() {
+    for model in reaction::builtin_models() {
+        for seeder in [
+            Seeder::GradientNoise,
+            Seeder::WhiteNoise {
+                mean: 0.5,
+                amplitude: 0.5,
+            },
+        ] {
+            for seed in 1..=2 {
+                let mut simulation = Simulation::new(16, 16, model.clone());
+                simulation.seeder = seeder;
+                simulation.set_seed(seed);
+                simulation.reset();
+                for step in 0..500 {
+                    simulation.step();
+                    assert!(
+                        simulation.check().is_ok(),
+                        "{} from {} with seed {seed} blew up at step {step}",
+                        model.name(),
+                        seeder.name()
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn auto_substep_keeps_fast_diffusion_stable() {
+    let mut simulation = pure_diffusion(1);
+    simulation.channels[0].diffusion_coefficient = 1.0;
+    simulation.step();
+    assert_eq!(simulation.substeps(), 1);
+    simulation.auto_substep = true;
+    let limit = simulation.stable_time_step().unwrap();
+    assert!(limit < simulation.dt);
+    for _ in 0..100 {
+        simulation.step();
+        assert!(simulation.check().is_ok());
+    }
+    assert_eq!(
+        simulation.substeps(),
+        (simulation.dt / limit).ceil() as usize
+    );
+}
+
+#[test]
+fn chemotaxis_limit_ignores_attractant_behind_walls() {
+    let mut simulation = pure_diffusion(2);
+    simulation.seeder = Seeder::Uniform { value: 0.0 };
+    simulation.reset();
+    simulation.auto_substep = true;
+    let without = simulation.stable_time_step();
+    simulation.chemotaxis = Some(Chemotaxis {
+        sensitivity: 50.0,
+        ..Chemotaxis::default()
+    });
+    for y in 0..24 {
+        simulation.paint_domain(16, y, 0, CellKind::Wall, &[]);
+        simulation.grid.set(16, y as usize, 1, 1.0);
+    }
+    // No open cell sees the attractant held by the wall, measured either
+    // before the first step or during it.
+    assert_eq!(simulation.stable_time_step(), without);
+    simulation.step();
+    simulation.grid.set(16, 0, 1, 1.0);
+    assert_eq!(simulation.stable_time_step(), without);
+    // An open attractant peak does tighten the limit.
+    simulation.paint(6, 6, 2, &[0.0, 1.0]);
+    assert!(simulation.stable_time_step() < without);
+}
+
+#[test]
+fn lenia_adds_channels_past_the_reaction_model() {
+    let mut simulation = Simulation::new(32, 32, Arc::new(GrayScott));
+    simulation.set_automaton(Automaton::Lenia(Lenia::default()));
+    simulation.set_channel_count(3);
+    assert_eq!(simulation.channel_count(), 3);
+    assert_eq!(simulation.channels.len(), 3);
+    simulation.step();
+    assert!(simulation.check().is_ok());
+}
+
+#[test]
+fn still_life_runs_without_tripping_the_stability_check() {
+    let mut simulation = Simulation::new(8, 8, Arc::new(GrayScott));
+    simulation.set_automaton(Automaton::Life(LifeRule::default()));
+    simulation.seeder = Seeder::Uniform { value: 0.0 };
+    simulation.reset();
+    // A block.
+    for (x, y) in [(3, 3), (4, 3), (3, 4), (4, 4)] {
+        simulation.grid.set(x, y, 0, 1.0);
+    }
+    for _ in 0..100_001 {
+        simulation.step();
+    }
+    assert!(simulation.grid.get(3, 3, 1) > 1e3);
+    assert!(simulation.check().is_ok());
+}
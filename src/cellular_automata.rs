@@ -1,144 +1,1082 @@
 use bevy::prelude::*;
 use bevy_egui::{egui, EguiContexts};
-use rand::distributions::Distribution;
+use simulation::{
+    reaction, AdvectionScheme, Automaton, Boundary, CellKind, ChannelParameters, Chemotaxis, Flow,
+    Grid, Integrator, Kernel, Lenia, LifeRule, MapProfile, ParameterMap, ParameterSpec,
+    ParameterTarget, Seeder, Simulation, Stencil,
+};
+use std::sync::Arc;
 pub(crate) mod height_map;
 pub(crate) mod state;
-mod torus_topology;
 
 pub fn egui_system(
     mut contexts: EguiContexts,
     mut params: ResMut<state::CellularSystemState>,
     mut timer: ResMut<Time<Fixed>>,
 ) {
-    if params.painting {
+    if params.painting && params.history_position.is_none() {
         params.paint();
     }
     egui::Window::new("Control").show(contexts.ctx_mut(), |ui| {
         ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
-            ui.checkbox(&mut params.iterating, "keep runnning at");
+            if ui
+                .checkbox(&mut params.iterating, "keep runnning at")
+                .changed()
+            {
+                params.pending_steps = 0;
+            }
             let fps_slider = ui.add(egui::Slider::new(&mut params.fps, 1.0..=120.0).text("FPS"));
             if fps_slider.dragged() {
                 timer.set_timestep_hz(params.fps);
             }
         });
+        ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
+            if ui.button("Step").clicked() {
+                params.run_steps(1);
+            }
+            if ui.button("Run").clicked() {
+                let steps = params.run_length;
+                params.run_steps(steps);
+            }
+            ui.add(
+                egui::DragValue::new(&mut params.run_length)
+                    .clamp_range(1..=1_000_000)
+                    .suffix(" iterations"),
+            );
+            ui.add(
+                egui::DragValue::new(&mut params.steps_per_frame)
+                    .clamp_range(1..=1000)
+                    .suffix(" steps per frame"),
+            );
+        });
+        ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
+            ui.label(format!("Iteration {}", params.iterations_done()));
+            if params.pending_steps > 0 {
+                ui.label(format!("({} to go)", params.pending_steps));
+            }
+        });
+        if let Some(instability) = &params.instability {
+            ui.colored_label(ui.visuals().error_fg_color, instability);
+        }
         ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
             if ui.button("Reset Map").clicked() {
                 params.resetting = true;
             }
             if ui.button("Random Rules").clicked() {
-                params.red = random_channel_parameters();
-                params.green = random_channel_parameters();
-                params.blue = random_channel_parameters();
+                params.simulation.randomize_rules();
             };
             if ui.button("Reset Map & Random Rules").clicked() {
                 params.resetting = true;
-                params.red = random_channel_parameters();
-                params.green = random_channel_parameters();
-                params.blue = random_channel_parameters();
+                params.simulation.randomize_rules();
             }
         });
+        add_seeder_ui(&mut params.simulation.seeder, ui);
         ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
-            ui.heading("Heightmap");
-            if ui
-                .add(egui::RadioButton::new(params.render_channel == 0, "Red"))
-                .clicked()
-            {
-                params.render_channel = 0;
+            ui.heading("Seed");
+            let mut seed = params.simulation.seed();
+            if ui.add(egui::DragValue::new(&mut seed)).changed() {
+                params.simulation.set_seed(seed);
+                params.resetting = true;
+            }
+            if ui.button("New Seed").clicked() {
+                params.simulation.set_seed(Simulation::random_seed());
+                params.resetting = true;
             }
+        });
+        ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
+            ui.heading("Mode");
+            let mut selected = None;
+            egui::ComboBox::from_id_source("automaton")
+                .selected_text(params.simulation.automaton().name())
+                .show_ui(ui, |ui| {
+                    for automaton in [
+                        Automaton::ReactionDiffusion,
+                        Automaton::Lenia(Lenia::default()),
+                        Automaton::Life(LifeRule::default()),
+                    ] {
+                        let current = std::mem::discriminant(params.simulation.automaton())
+                            == std::mem::discriminant(&automaton);
+                        if ui.selectable_label(current, automaton.name()).clicked() && !current {
+                            selected = Some(automaton);
+                        }
+                    }
+                });
+            if let Some(automaton) = selected {
+                params.set_automaton(automaton);
+            }
+            ui.heading("Channels");
+            let mut channels = params.simulation.channel_count();
             if ui
-                .add(egui::RadioButton::new(params.render_channel == 1, "Green"))
-                .clicked()
+                .add_enabled(
+                    params.simulation.fixed_channel_count().is_none(),
+                    egui::DragValue::new(&mut channels).clamp_range(1..=8),
+                )
+                .changed()
             {
-                params.render_channel = 1;
+                params.set_channel_count(channels);
             }
+        });
+        add_boundary_ui(&mut params.simulation.boundary, ui);
+        ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
+            ui.heading("Grid");
+            for size in params.grid_size.iter_mut() {
+                ui.add(egui::DragValue::new(size).clamp_range(8..=1024));
+            }
+            let grid = &params.simulation.grid;
+            let resized = params.grid_size != [grid.width(), grid.height()];
             if ui
-                .add(egui::RadioButton::new(params.render_channel == 2, "Blue"))
+                .add_enabled(resized, egui::Button::new("Resize"))
                 .clicked()
             {
-                params.render_channel = 2;
+                params.resize_grid();
+            }
+        });
+        match params.simulation.automaton_mut() {
+            Automaton::ReactionDiffusion => add_reaction_diffusion_ui(&mut params, ui),
+            Automaton::Lenia(lenia) => add_lenia_ui(lenia, ui),
+            Automaton::Life(_) => add_life_ui(&mut params, ui),
+        }
+        ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
+            ui.heading("Heightmap");
+            for channel in 0..params.simulation.channel_count() {
+                if ui
+                    .add(egui::RadioButton::new(
+                        params.render_channel == Some(channel),
+                        params.channel_name(channel),
+                    ))
+                    .clicked()
+                {
+                    params.render_channel = Some(channel);
+                }
             }
             if ui
-                .add(egui::RadioButton::new(params.render_channel == 3, "Sum"))
+                .add(egui::RadioButton::new(
+                    params.render_channel.is_none(),
+                    "Sum",
+                ))
                 .clicked()
             {
-                params.render_channel = 3;
+                params.render_channel = None;
             }
         });
         ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
             ui.heading("Paint");
-            ui.color_edit_button_rgb(&mut params.paint_color);
+            // Life always paints live cells.
+            if !matches!(params.simulation.automaton(), Automaton::Life(_)) {
+                for value in params.paint_values.iter_mut() {
+                    ui.add(
+                        egui::DragValue::new(value)
+                            .clamp_range(0.0..=1.0)
+                            .speed(0.01),
+                    );
+                }
+            }
             ui.add(egui::Slider::new(&mut params.paint_radius, 1..=100).text("px Radius"));
+            if matches!(params.simulation.advection.flow, Flow::Fluid(_)) {
+                ui.checkbox(&mut params.stirring, "Stir fluid");
+                if params.stirring {
+                    ui.add(
+                        egui::DragValue::new(&mut params.stir_strength)
+                            .clamp_range(0.0..=10.0)
+                            .speed(0.01)
+                            .prefix("strength "),
+                    );
+                }
+            }
+        });
+        egui::CollapsingHeader::new("History").show(ui, |ui| {
+            add_history_ui(&mut params, ui);
         });
         egui::warn_if_debug_build(ui);
 
-        let new_name = params.texture_handle.to_string();
-        let new_image = params.new_texture.clone();
+        let image = params.image.clone();
         let raw_size = params.canvas_size;
 
         let texture_handle_to_render = params.texture.get_or_insert_with(|| {
             ui.ctx()
-                .load_texture(new_name, new_image, Default::default())
+                .load_texture("simulation", image, Default::default())
         });
 
-        let img = ui.add(egui::widgets::Image::new(egui::load::SizedTexture::new(
-            texture_handle_to_render.id(),
-            bevy_egui::egui::Vec2::new(raw_size[0], raw_size[1]),
-        )));
+        let img = ui.add(
+            egui::widgets::Image::new(egui::load::SizedTexture::new(
+                texture_handle_to_render.id(),
+                bevy_egui::egui::Vec2::new(raw_size[0], raw_size[1]),
+            ))
+            .sense(egui::Sense::drag()),
+        );
 
         if let Some(pos) = img.hover_pos() {
             let min_pos = img.rect.min;
-            params.painting = true;
+            params.painting = !params.stirs();
             params.paint_pos = bevy_egui::egui::Pos2::new(pos.x - min_pos.x, pos.y - min_pos.y);
+            if params.stirs() && img.dragged() && params.history_position.is_none() {
+                params.stir(img.drag_delta());
+            }
         } else {
             params.painting = false;
         }
     });
-    egui::Window::new("Red").show(contexts.ctx_mut(), |ui| {
-        add_channel_ui(&mut params.red, ui, "Red".to_owned());
+    let params = params.into_inner();
+    let model = params.simulation.model().clone();
+    let reaction_diffusion = matches!(params.simulation.automaton(), Automaton::ReactionDiffusion);
+    let labels: Vec<String> = (0..params.simulation.channel_count())
+        .map(|channel| params.channel_name(channel))
+        .collect();
+    let mapped: Vec<ParameterTarget> = params
+        .simulation
+        .parameter_maps
+        .iter()
+        .map(|map| map.target)
+        .collect();
+    for (channel, ((parameters, color), label)) in params
+        .simulation
+        .channels
+        .iter_mut()
+        .zip(params.channel_colors.iter_mut())
+        .zip(labels)
+        .enumerate()
+    {
+        egui::Window::new(&label).show(contexts.ctx_mut(), |ui| {
+            ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
+                ui.label("Display color");
+                ui.color_edit_button_rgb(color);
+            });
+            if !reaction_diffusion {
+                return;
+            }
+            add_channel_ui(
+                parameters,
+                channel,
+                &mapped,
+                &model.diffusion(channel),
+                &model.channel_parameters(channel),
+                ui,
+                label,
+            );
+        });
+    }
+}
+
+fn add_history_ui(params: &mut state::CellularSystemState, ui: &mut egui::Ui) {
+    ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
+        ui.add(
+            egui::DragValue::new(&mut params.history.capacity)
+                .clamp_range(0..=4096)
+                .prefix("keep "),
+        );
+        ui.add(
+            egui::DragValue::new(&mut params.history.stride)
+                .clamp_range(1..=1000)
+                .prefix("frames, every "),
+        );
+        ui.label(format!(
+            "iterations ({} frames, {:.1} MB)",
+            params.history.len(),
+            params.history.bytes() as f64 / 1e6
+        ));
     });
-    egui::Window::new("Green").show(contexts.ctx_mut(), |ui| {
-        add_channel_ui(&mut params.green, ui, "Green".to_owned());
+    if params.history.is_empty() {
+        return;
+    }
+    let last = params.history.len() - 1;
+    let mut position = params.history_position.unwrap_or(last);
+    let iteration = params.history.iteration(position);
+    if ui
+        .add(egui::Slider::new(&mut position, 0..=last).text(format!("iteration {}", iteration)))
+        .changed()
+    {
+        params.scrub_to(position);
+    }
+    if params.history_position.is_some() {
+        ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
+            if ui.button("Resume from here").clicked() {
+                params.resume_from_history(false);
+            }
+            if ui.button("Branch").clicked() {
+                params.resume_from_history(true);
+            }
+            if ui.button("Back to latest").clicked() {
+                params.scrub_to(params.history.len() - 1);
+                params.resume_from_history(false);
+            }
+        });
+    }
+}
+
+fn add_reaction_diffusion_ui(params: &mut state::CellularSystemState, ui: &mut egui::Ui) {
+    ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
+        ui.heading("Model");
+        let current = params.simulation.model().clone();
+        let mut selected = None;
+        egui::ComboBox::from_id_source("model")
+            .selected_text(current.name())
+            .show_ui(ui, |ui| {
+                for model in params.models.iter() {
+                    if ui
+                        .selectable_label(Arc::ptr_eq(model, &current), model.name())
+                        .clicked()
+                    {
+                        selected = Some(model.clone());
+                    }
+                }
+            });
+        if let Some(model) = selected {
+            params.set_model(model);
+        }
+    });
+    let model = params.simulation.model().clone();
+    for (index, (spec, value)) in model
+        .parameters()
+        .iter()
+        .zip(params.simulation.parameters.iter_mut())
+        .enumerate()
+    {
+        let mapped = params
+            .simulation
+            .parameter_maps
+            .iter()
+            .any(|map| map.target == ParameterTarget::Global(index));
+        ui.add_enabled(
+            !mapped,
+            egui::Slider::new(value, spec.range.clone()).text(&spec.name),
+        );
+    }
+    ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
+        ui.heading("Stencil");
+        add_stencil_ui(&mut params.simulation.stencil, ui);
+    });
+    if let Stencil::Custom(kernel) = &mut params.simulation.stencil {
+        add_kernel_ui(kernel, ui);
+    }
+    add_integrator_ui(&mut params.simulation, ui);
+    add_advection_ui(params, ui);
+    egui::CollapsingHeader::new("Cross-Diffusion").show(ui, |ui| {
+        add_cross_diffusion_ui(params, ui);
+    });
+    egui::CollapsingHeader::new("Chemotaxis").show(ui, |ui| {
+        add_chemotaxis_ui(params, ui);
+    });
+    egui::CollapsingHeader::new("Parameter Maps").show(ui, |ui| {
+        add_parameter_maps_ui(params, ui);
     });
-    egui::Window::new("Blue").show(contexts.ctx_mut(), |ui| {
-        add_channel_ui(&mut params.blue, ui, "Blue".to_owned());
+    egui::CollapsingHeader::new("Domain").show(ui, |ui| {
+        add_domain_ui(params, ui);
+    });
+    egui::CollapsingHeader::new("Custom Equations").show(ui, |ui| {
+        add_equations_ui(params, ui);
     });
 }
 
-fn add_channel_ui(channel: &mut state::ChannelParameters, ui: &mut egui::Ui, label: String) {
-    ui.add(
-        egui::Slider::new(&mut channel.diffusion_coefficient, 0.0..=0.1)
-            .text(format!("{} Diffusion", label)),
+fn add_integrator_ui(simulation: &mut Simulation, ui: &mut egui::Ui) {
+    ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
+        ui.heading("Integrator");
+        let integrator = &mut simulation.integrator;
+        egui::ComboBox::from_id_source("integrator")
+            .selected_text(integrator.name())
+            .show_ui(ui, |ui| {
+                for preset in Integrator::PRESETS {
+                    let current =
+                        std::mem::discriminant(integrator) == std::mem::discriminant(&preset);
+                    if ui.selectable_label(current, preset.name()).clicked() && !current {
+                        *integrator = preset;
+                    }
+                }
+            });
+        ui.add(
+            egui::DragValue::new(&mut simulation.dt)
+                .clamp_range(0.001..=2.0)
+                .speed(0.01)
+                .prefix("dt "),
+        );
+        ui.checkbox(&mut simulation.auto_substep, "Auto-substep");
+        if let Integrator::Adaptive { tolerance } = &mut simulation.integrator {
+            ui.add(
+                egui::Slider::new(tolerance, 1e-6..=1e-1)
+                    .logarithmic(true)
+                    .text("Tolerance"),
+            );
+        }
+        if simulation.substeps() > 1 {
+            ui.label(format!("{} steps", simulation.substeps()));
+        }
+    });
+    // With auto-substep on the step keeps to the limit by itself.
+    if simulation.auto_substep {
+        return;
+    }
+    if let Some(limit) = simulation.stable_time_step() {
+        if simulation.dt > limit {
+            ui.colored_label(
+                ui.visuals().warn_fg_color,
+                format!(
+                    "dt exceeds the stability limit {:.3} of this stencil and diffusion",
+                    limit
+                ),
+            );
+        }
+    }
+}
+
+// Every parameter of the model a map can stand in for, with its label.
+fn mappable_parameters(
+    params: &state::CellularSystemState,
+) -> Vec<(ParameterTarget, String, ParameterSpec)> {
+    let model = params.simulation.model();
+    let mut parameters: Vec<_> = model
+        .parameters()
+        .into_iter()
+        .enumerate()
+        .map(|(index, spec)| (ParameterTarget::Global(index), spec.name.clone(), spec))
+        .collect();
+    for channel in 0..params.simulation.channel_count() {
+        let label = params.channel_name(channel);
+        let diffusion = model.diffusion(channel);
+        parameters.push((
+            ParameterTarget::Diffusion(channel),
+            format!("{} {}", label, diffusion.name),
+            diffusion,
+        ));
+        for (index, spec) in model.channel_parameters(channel).into_iter().enumerate() {
+            parameters.push((
+                ParameterTarget::Channel { channel, index },
+                format!("{} {}", label, spec.name),
+                spec,
+            ));
+        }
+    }
+    parameters
+}
+
+fn add_parameter_maps_ui(params: &mut state::CellularSystemState, ui: &mut egui::Ui) {
+    let parameters = mappable_parameters(params);
+    let (width, height) = (
+        params.simulation.grid.width(),
+        params.simulation.grid.height(),
     );
+    let mut removed = None;
+    let mut load_image = None;
+    for (index, map) in params.simulation.parameter_maps.iter_mut().enumerate() {
+        let Some((_, name, spec)) = parameters.iter().find(|(target, ..)| *target == map.target)
+        else {
+            continue;
+        };
+        ui.push_id(index, |ui| {
+            ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
+                ui.heading(name);
+                egui::ComboBox::from_id_source("profile")
+                    .selected_text(map.profile.name())
+                    .show_ui(ui, |ui| {
+                        let profiles = [
+                            MapProfile::Gradient { angle: 0.0 },
+                            MapProfile::Radial,
+                            MapProfile::Image(Grid::new(1, 1, 1)),
+                            MapProfile::Painted(Grid::new(width, height, 1)),
+                        ];
+                        for profile in profiles.into_iter().filter(|profile| {
+                            state::CAN_READ_IMAGES || !matches!(profile, MapProfile::Image(_))
+                        }) {
+                            let current = std::mem::discriminant(&map.profile)
+                                == std::mem::discriminant(&profile);
+                            if ui.selectable_label(current, profile.name()).clicked() && !current {
+                                map.profile = profile;
+                            }
+                        }
+                    });
+                if ui.button("Remove").clicked() {
+                    removed = Some(index);
+                }
+            });
+            ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
+                let speed = (spec.range.end() - spec.range.start()) / 200.0;
+                for (value, prefix) in [(&mut map.from, "from "), (&mut map.to, "to ")] {
+                    ui.add(
+                        egui::DragValue::new(value)
+                            .clamp_range(spec.range.clone())
+                            .speed(speed)
+                            .prefix(prefix),
+                    );
+                }
+                match &mut map.profile {
+                    MapProfile::Gradient { angle } => {
+                        ui.label("angle");
+                        ui.drag_angle(angle);
+                    }
+                    MapProfile::Radial => {}
+                    MapProfile::Image(_) => {
+                        ui.text_edit_singleline(&mut params.map_image_path);
+                        if ui.button("Load").clicked() {
+                            load_image = Some(index);
+                        }
+                    }
+                    MapProfile::Painted(_) => {
+                        let mut brush = params.paint_map == Some(index);
+                        if ui.checkbox(&mut brush, "Brush").changed() {
+                            params.paint_map = brush.then_some(index);
+                        }
+                        if brush {
+                            ui.add(
+                                egui::Slider::new(&mut params.map_paint_weight, 0.0..=1.0)
+                                    .text("weight"),
+                            );
+                        }
+                    }
+                }
+            });
+        });
+    }
+    if let Some(index) = removed {
+        params.simulation.parameter_maps.remove(index);
+        params.paint_map = None;
+    }
+    if let Some(index) = load_image {
+        params.load_map_image(index);
+    }
+    egui::ComboBox::from_id_source("add_parameter_map")
+        .selected_text("Add map")
+        .show_ui(ui, |ui| {
+            for (target, name, spec) in parameters.iter() {
+                let mapped = params
+                    .simulation
+                    .parameter_maps
+                    .iter()
+                    .any(|map| map.target == *target);
+                if !mapped && ui.selectable_label(false, name).clicked() {
+                    params.simulation.parameter_maps.push(ParameterMap::new(
+                        *target,
+                        *spec.range.start(),
+                        *spec.range.end(),
+                    ));
+                }
+            }
+        });
+    if let Some(error) = &params.map_error {
+        ui.colored_label(ui.visuals().error_fg_color, error);
+    }
+}
+
+fn add_domain_ui(params: &mut state::CellularSystemState, ui: &mut egui::Ui) {
+    ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
+        ui.label("Brush");
+        ui.selectable_value(&mut params.domain_brush, None, "Paint field");
+        for kind in CellKind::PRESETS {
+            ui.selectable_value(&mut params.domain_brush, Some(kind), kind.name());
+        }
+        if ui.button("Clear").clicked() {
+            params.simulation.domain.clear();
+        }
+    });
+    if params.domain_brush == Some(CellKind::Fixed) {
+        ui.label("Sources and sinks hold the paint values.");
+    }
+    if state::CAN_READ_IMAGES {
+        ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
+            ui.text_edit_singleline(&mut params.domain_image_path);
+            if ui.button("Load").clicked() {
+                params.load_domain_image();
+            }
+        });
+        ui.label("Black: wall, red: source at 1, blue: sink at 0.");
+    }
+    if let Some(error) = &params.domain_error {
+        ui.colored_label(ui.visuals().error_fg_color, error);
+    }
+}
+
+fn add_advection_ui(params: &mut state::CellularSystemState, ui: &mut egui::Ui) {
+    let mut load_image = None;
+    ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
+        ui.heading("Flow");
+        let advection = &mut params.simulation.advection;
+        egui::ComboBox::from_id_source("flow")
+            .selected_text(advection.flow.name())
+            .show_ui(ui, |ui| {
+                let flows = [
+                    Flow::Still,
+                    Flow::Uniform {
+                        velocity: [0.2, 0.0],
+                    },
+                    Flow::Vortex {
+                        angular_speed: 0.02,
+                        radius: 40.0,
+                    },
+                    Flow::Shear { speed: 0.2 },
+                    Flow::Image {
+                        field: Grid::new(1, 1, 2),
+                        scale: 0.5,
+                    },
+                    Flow::Fluid(Box::default()),
+                ];
+                for flow in flows
+                    .into_iter()
+                    .filter(|flow| state::CAN_READ_IMAGES || !matches!(flow, Flow::Image { .. }))
+                {
+                    let current =
+                        std::mem::discriminant(&advection.flow) == std::mem::discriminant(&flow);
+                    if ui.selectable_label(current, flow.name()).clicked() && !current {
+                        advection.flow = flow;
+                    }
+                }
+            });
+        if advection.flow.is_still() {
+            return;
+        }
+        egui::ComboBox::from_id_source("advection_scheme")
+            .selected_text(advection.scheme.name())
+            .show_ui(ui, |ui| {
+                for scheme in AdvectionScheme::PRESETS {
+                    ui.selectable_value(&mut advection.scheme, scheme, scheme.name());
+                }
+            });
+        match &mut advection.flow {
+            Flow::Still => {}
+            Flow::Uniform { velocity } => {
+                let [vx, vy] = velocity;
+                ui.add(velocity_value(vx, "vx "));
+                ui.add(velocity_value(vy, "vy "));
+            }
+            Flow::Vortex {
+                angular_speed,
+                radius,
+            } => {
+                ui.add(
+                    egui::DragValue::new(angular_speed)
+                        .clamp_range(-0.5..=0.5)
+                        .speed(0.001)
+                        .prefix("angular speed "),
+                );
+                ui.add(
+                    egui::DragValue::new(radius)
+                        .clamp_range(1.0..=1024.0)
+                        .prefix("radius "),
+                );
+            }
+            Flow::Shear { speed } => {
+                ui.add(velocity_value(speed, "speed "));
+            }
+            Flow::Image { scale, .. } => {
+                ui.add(velocity_value(scale, "scale "));
+                ui.text_edit_singleline(&mut params.flow_image_path);
+                if ui.button("Load").clicked() {
+                    load_image = Some(*scale);
+                }
+            }
+            Flow::Fluid(fluid) => {
+                ui.add(
+                    egui::DragValue::new(&mut fluid.viscosity)
+                        .clamp_range(0.0..=10.0)
+                        .speed(0.01)
+                        .prefix("viscosity "),
+                );
+                if ui.button("Calm").clicked() {
+                    fluid.clear();
+                }
+            }
+        }
+    });
+    if let Some(scale) = load_image {
+        params.load_flow_image(scale);
+    }
+    if let Some(error) = &params.flow_error {
+        ui.colored_label(ui.visuals().error_fg_color, error);
+    }
+}
+
+fn add_lenia_ui(lenia: &mut Lenia, ui: &mut egui::Ui) {
+    ui.add(egui::Slider::new(&mut lenia.radius, 2.0..=60.0).text("Kernel Radius"));
+    ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
+        ui.label("Rings");
+        for peak in lenia.peaks.iter_mut() {
+            ui.add(
+                egui::DragValue::new(peak)
+                    .clamp_range(0.0..=1.0)
+                    .speed(0.01),
+            );
+        }
+        if ui
+            .add_enabled(lenia.peaks.len() < 4, egui::Button::new("+"))
+            .clicked()
+        {
+            lenia.peaks.push(1.0);
+        }
+        if ui
+            .add_enabled(lenia.peaks.len() > 1, egui::Button::new("-"))
+            .clicked()
+        {
+            lenia.peaks.pop();
+        }
+    });
+    ui.add(egui::Slider::new(&mut lenia.growth_center, 0.0..=0.5).text("Growth Center"));
     ui.add(
-        egui::Slider::new(&mut channel.growth_rate, 0.7..=1.0).text(format!("{} Growth", label)),
+        egui::Slider::new(&mut lenia.growth_width, 0.001..=0.1)
+            .logarithmic(true)
+            .text("Growth Width"),
     );
-    ui.add(
-        egui::Slider::new(&mut channel.interaction_coefficient, 0.0..=5.0)
-            .text(format!("{} Interaction", label)),
+    ui.add(egui::Slider::new(&mut lenia.time_step, 0.01..=1.0).text("Time Step"));
+}
+
+const LIFE_PRESETS: [(&str, &str); 8] = [
+    ("Conway", "B3/S23"),
+    ("HighLife", "B36/S23"),
+    ("Day & Night", "B3678/S34678"),
+    ("Seeds", "B2/S"),
+    ("Brian's Brain", "B2/S/C3"),
+    ("Star Wars", "345/2/4"),
+    ("Bosco", "R5,C0,M1,S34..58,B34..45,NM"),
+    ("Majority", "R4,C0,M1,S41..81,B41..81,NM"),
+];
+
+fn add_life_ui(params: &mut state::CellularSystemState, ui: &mut egui::Ui) {
+    ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
+        ui.heading("Rule");
+        let response = ui.text_edit_singleline(&mut params.life_rule_source);
+        if ui.button("Apply").clicked()
+            || (response.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter)))
+        {
+            params.apply_life_rule();
+        }
+        egui::ComboBox::from_id_source("life_presets")
+            .selected_text("Presets")
+            .show_ui(ui, |ui| {
+                for (name, rule) in LIFE_PRESETS {
+                    if ui.selectable_label(false, name).clicked() {
+                        params.life_rule_source = rule.to_owned();
+                        params.apply_life_rule();
+                    }
+                }
+            });
+    });
+    if let Some(error) = &params.life_rule_error {
+        ui.colored_label(ui.visuals().error_fg_color, error);
+    }
+}
+
+fn add_boundary_ui(boundary: &mut Boundary, ui: &mut egui::Ui) {
+    ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
+        ui.heading("Boundary");
+        egui::ComboBox::from_id_source("boundary")
+            .selected_text(boundary.name())
+            .show_ui(ui, |ui| {
+                for preset in Boundary::PRESETS {
+                    let current =
+                        std::mem::discriminant(boundary) == std::mem::discriminant(&preset);
+                    if ui.selectable_label(current, preset.name()).clicked() && !current {
+                        *boundary = preset;
+                    }
+                }
+            });
+        if let Boundary::Dirichlet(value) = boundary {
+            ui.add(
+                egui::DragValue::new(value)
+                    .speed(0.01)
+                    .clamp_range(0.0..=1.0)
+                    .prefix("value "),
+            );
+        }
+    });
+}
+
+fn add_seeder_ui(seeder: &mut Seeder, ui: &mut egui::Ui) {
+    ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
+        ui.heading("Initial Field");
+        egui::ComboBox::from_id_source("seeder")
+            .selected_text(seeder.name())
+            .show_ui(ui, |ui| {
+                for preset in Seeder::PRESETS {
+                    let current = std::mem::discriminant(seeder) == std::mem::discriminant(&preset);
+                    if ui.selectable_label(current, preset.name()).clicked() && !current {
+                        *seeder = preset;
+                    }
+                }
+            });
+        match seeder {
+            Seeder::GradientNoise => {}
+            Seeder::Uniform { value: level } => {
+                ui.add(unit_value(level, "value "));
+            }
+            Seeder::WhiteNoise { mean, amplitude } => {
+                ui.add(unit_value(mean, "mean "));
+                ui.add(unit_value(amplitude, "amplitude "));
+            }
+            Seeder::Perlin { scale, octaves } => {
+                ui.add(
+                    egui::DragValue::new(scale)
+                        .clamp_range(2.0..=256.0)
+                        .prefix("scale "),
+                );
+                ui.add(
+                    egui::DragValue::new(octaves)
+                        .clamp_range(1..=6)
+                        .prefix("octaves "),
+                );
+            }
+            Seeder::Spots {
+                count,
+                radius,
+                value: level,
+            } => {
+                ui.add(
+                    egui::DragValue::new(count)
+                        .clamp_range(1..=500)
+                        .prefix("count "),
+                );
+                ui.add(
+                    egui::DragValue::new(radius)
+                        .clamp_range(1..=50)
+                        .prefix("radius "),
+                );
+                ui.add(unit_value(level, "value "));
+            }
+            Seeder::Stripes { wavelength, angle } => {
+                ui.add(
+                    egui::DragValue::new(wavelength)
+                        .clamp_range(2.0..=256.0)
+                        .prefix("wavelength "),
+                );
+                ui.label("angle");
+                ui.drag_angle(angle);
+            }
+            Seeder::Disk {
+                radius,
+                value: level,
+            } => {
+                ui.add(unit_value(radius, "radius "));
+                ui.add(unit_value(level, "value "));
+            }
+            Seeder::Checkerboard { size } => {
+                ui.add(
+                    egui::DragValue::new(size)
+                        .clamp_range(1..=256)
+                        .prefix("size "),
+                );
+            }
+            Seeder::Perturb { amplitude } => {
+                ui.add(unit_value(amplitude, "amplitude "));
+            }
+        }
+    });
+}
+
+fn velocity_value<'a>(value: &'a mut f32, prefix: &str) -> egui::DragValue<'a> {
+    egui::DragValue::new(value)
+        .clamp_range(-5.0..=5.0)
+        .speed(0.01)
+        .prefix(prefix)
+}
+
+fn unit_value<'a>(value: &'a mut f32, prefix: &str) -> egui::DragValue<'a> {
+    egui::DragValue::new(value)
+        .clamp_range(0.0..=1.0)
+        .speed(0.01)
+        .prefix(prefix)
+}
+
+fn add_stencil_ui(stencil: &mut Stencil, ui: &mut egui::Ui) {
+    let mut selected = None;
+    egui::ComboBox::from_id_source("stencil")
+        .selected_text(stencil.name())
+        .show_ui(ui, |ui| {
+            for preset in Stencil::PRESETS {
+                if ui
+                    .selectable_label(*stencil == preset, preset.name())
+                    .clicked()
+                {
+                    selected = Some(preset);
+                }
+            }
+            for size in [3, 5] {
+                let custom = matches!(stencil, Stencil::Custom(kernel) if kernel.size() == size);
+                if ui
+                    .selectable_label(custom, format!("Custom {0}x{0}", size))
+                    .clicked()
+                    && !custom
+                {
+                    selected = Some(Stencil::Custom(stencil.to_kernel(size)));
+                }
+            }
+        });
+    if let Some(new_stencil) = selected {
+        *stencil = new_stencil;
+    }
+    ui.label(format!("self-weight {:.3}", -stencil.self_weight()));
+}
+
+fn add_kernel_ui(kernel: &mut Kernel, ui: &mut egui::Ui) {
+    let radius = kernel.radius();
+    let self_weight = Stencil::Custom(kernel.clone()).self_weight();
+    egui::Grid::new("kernel").show(ui, |ui| {
+        for dy in -radius..=radius {
+            for dx in -radius..=radius {
+                if (dx, dy) == (0, 0) {
+                    ui.label(format!("{:.3}", -self_weight));
+                } else {
+                    let mut weight = kernel.weight(dx, dy);
+                    if ui
+                        .add(egui::DragValue::new(&mut weight).speed(0.01))
+                        .changed()
+                    {
+                        kernel.set_weight(dx, dy, weight);
+                    }
+                }
+            }
+            ui.end_row();
+        }
+    });
+}
+
+fn add_cross_diffusion_ui(params: &mut state::CellularSystemState, ui: &mut egui::Ui) {
+    ui.label("Row i, column j: how strongly the Laplacian of j drives i.");
+    let names: Vec<String> = (0..params.simulation.channel_count())
+        .map(|channel| params.channel_name(channel))
+        .collect();
+    egui::Grid::new("cross_diffusion").show(ui, |ui| {
+        ui.label("");
+        for name in names.iter() {
+            ui.label(name);
+        }
+        ui.end_row();
+        let model = params.simulation.model().clone();
+        for (channel, parameters) in params.simulation.channels.iter_mut().enumerate() {
+            ui.label(&names[channel]);
+            parameters.cross_diffusion.resize(names.len(), 0.0);
+            // The diagonal keeps to the range of the diffusion slider, the
+            // cross terms to as much either way, as they may drive a channel
+            // down a gradient as well as up.
+            let range = model.diffusion(channel).range;
+            let (start, end) = (*range.start(), *range.end());
+            let speed = (end - start) / 200.0;
+            for source in 0..names.len() {
+                let (value, range) = if source == channel {
+                    (&mut parameters.diffusion_coefficient, range.clone())
+                } else {
+                    (&mut parameters.cross_diffusion[source], -end..=end)
+                };
+                ui.add(egui::DragValue::new(value).clamp_range(range).speed(speed));
+            }
+            ui.end_row();
+        }
+    });
+}
+
+fn add_chemotaxis_ui(params: &mut state::CellularSystemState, ui: &mut egui::Ui) {
+    let names: Vec<String> = (0..params.simulation.channel_count())
+        .map(|channel| params.channel_name(channel))
+        .collect();
+    let mut enabled = params.simulation.chemotaxis.is_some();
+    if ui
+        .checkbox(&mut enabled, "Cells follow an attractant")
+        .changed()
+    {
+        params.simulation.chemotaxis = enabled.then(Chemotaxis::default);
+    }
+    let Some(chemotaxis) = &mut params.simulation.chemotaxis else {
+        return;
+    };
+    ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
+        for (label, channel) in [
+            ("Cells", &mut chemotaxis.cells),
+            ("Attractant", &mut chemotaxis.attractant),
+        ] {
+            ui.label(label);
+            egui::ComboBox::from_id_source(label)
+                .selected_text(names.get(*channel).map_or("-", String::as_str))
+                .show_ui(ui, |ui| {
+                    for (index, name) in names.iter().enumerate() {
+                        ui.selectable_value(channel, index, name);
+                    }
+                });
+        }
+    });
+    ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
+        ui.add(
+            egui::DragValue::new(&mut chemotaxis.sensitivity)
+                .clamp_range(-20.0..=20.0)
+                .speed(0.01)
+                .prefix("sensitivity "),
+        );
+        ui.add(
+            egui::DragValue::new(&mut chemotaxis.saturation)
+                .clamp_range(0.0..=100.0)
+                .speed(0.01)
+                .prefix("saturation "),
+        );
+    });
+    if !chemotaxis.fits(names.len()) {
+        ui.colored_label(
+            ui.visuals().error_fg_color,
+            "Pick cells and attractant among the current channels.",
+        );
+    }
+}
+
+fn add_equations_ui(params: &mut state::CellularSystemState, ui: &mut egui::Ui) {
+    for (channel, source) in params.equation_sources.iter_mut().enumerate() {
+        ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
+            ui.label(format!("d{}/dt =", reaction::CHANNEL_VARIABLES[channel]));
+            ui.text_edit_singleline(source);
+        });
+    }
+    ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
+        let equations = params.equation_sources.len();
+        if ui
+            .add_enabled(
+                equations < reaction::CHANNEL_VARIABLES.len(),
+                egui::Button::new("Add Equation"),
+            )
+            .clicked()
+        {
+            params.equation_sources.push(String::new());
+        }
+        if ui
+            .add_enabled(equations > 1, egui::Button::new("Remove Equation"))
+            .clicked()
+        {
+            params.equation_sources.pop();
+        }
+        if ui.button("Apply").clicked() {
+            params.apply_equations();
+        }
+    });
+    if let Some(error) = &params.equation_error {
+        ui.colored_label(ui.visuals().error_fg_color, error);
+    }
+}
+
+fn add_channel_ui(
+    parameters: &mut ChannelParameters,
+    channel: usize,
+    mapped: &[ParameterTarget],
+    diffusion: &ParameterSpec,
+    specs: &[ParameterSpec],
+    ui: &mut egui::Ui,
+    label: String,
+) {
+    ui.add_enabled(
+        !mapped.contains(&ParameterTarget::Diffusion(channel)),
+        egui::Slider::new(
+            &mut parameters.diffusion_coefficient,
+            diffusion.range.clone(),
+        )
+        .text(format!("{} {}", label, diffusion.name)),
     );
+    for (index, (spec, value)) in specs.iter().zip(parameters.values.iter_mut()).enumerate() {
+        ui.add_enabled(
+            !mapped.contains(&ParameterTarget::Channel { channel, index }),
+            egui::Slider::new(value, spec.range.clone()).text(format!("{} {}", label, spec.name)),
+        );
+    }
+    let noise = &mut parameters.noise;
     ui.add(
-        egui::Slider::new(&mut channel.saturation_constant, 0.0..=3.0)
-            .text(format!("{} Saturation", label)),
+        egui::Slider::new(&mut noise.additive, 0.0..=0.1).text(format!("{} additive noise", label)),
     );
     ui.add(
-        egui::Slider::new(&mut channel.feedback_coefficient, 0.0..=3.0)
-            .text(format!("{} Feedback", label)),
+        egui::Slider::new(&mut noise.multiplicative, 0.0..=0.5)
+            .text(format!("{} multiplicative noise", label)),
     );
-}
-
-fn random_channel_parameters() -> state::ChannelParameters {
-    let mut rng = rand::thread_rng();
-    let p0 = rand::distributions::Uniform::new_inclusive(0.0, 0.1);
-    let p1 = rand::distributions::Uniform::new_inclusive(0.7, 1.0);
-    let p2 = rand::distributions::Uniform::new_inclusive(0.0, 5.0);
-    let p3 = rand::distributions::Uniform::new_inclusive(0.0, 3.0);
-    let p4 = rand::distributions::Uniform::new_inclusive(0.0, 3.0);
-    state::ChannelParameters {
-        diffusion_coefficient: p0.sample(&mut rng),
-        growth_rate: p1.sample(&mut rng),
-        interaction_coefficient: p2.sample(&mut rng),
-        saturation_constant: p3.sample(&mut rng),
-        feedback_coefficient: p4.sample(&mut rng),
+    if noise.is_active() {
+        ui.add(
+            egui::Slider::new(&mut noise.correlation_length, 0.0..=10.0)
+                .text(format!("{} noise correlation length", label)),
+        );
     }
 }
 
@@ -162,16 +1100,12 @@ pub fn setup_3d_scene(
         transform: Transform::from_xyz(-1.0, 3.5, 6.0).looking_at(Vec3::ZERO, Vec3::Y),
         ..default()
     });
-    let height_map = height_map::height_map(params, 5.5);
-
-    let mut height_mesh = Mesh::new(bevy::render::render_resource::PrimitiveTopology::TriangleList);
-
-    height_mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, height_map.vertices);
-    height_mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, height_map.normals);
-    height_mesh.set_indices(Some(bevy::render::mesh::Indices::U32(height_map.indices)));
-
-    let height_mesh_handle = meshes.add(height_mesh);
+    let height_mesh_handle = meshes.add(height_map::height_map_mesh(&params));
     mesh.mesh = Some(height_mesh_handle.clone());
+    mesh.size = (
+        params.simulation.grid.width(),
+        params.simulation.grid.height(),
+    );
 
     commands.spawn(PbrBundle {
         mesh: height_mesh_handle,
@@ -1,37 +1,58 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, render::mesh::VertexAttributeValues};
 
-use super::{state::CellularSystemState, torus_topology};
+use simulation::render::{write_heights, write_normals};
+
+use super::state::CellularSystemState;
 
 pub struct HeightMapMeshData {
-    pub vertices: Vec<Vec3>,
-    pub normals: Vec<Vec3>,
+    pub vertices: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
     pub indices: Vec<u32>,
 }
 
-pub fn height_map(params: Res<CellularSystemState>, size: f32) -> HeightMapMeshData {
-    let vertices: Vec<Vec3> = params
-        .new_texture
-        .pixels
-        .iter()
-        .enumerate()
-        .map(|(i, pixel)| {
-            let height_value = match params.render_channel {
-                0 => pixel.r(),
-                1 => pixel.g(),
-                2 => pixel.b(),
-                _ => ((pixel.r() as f32 + pixel.g() as f32 + pixel.b() as f32) / 3.0) as u8,
-            };
+// Edge length of the heightmap mesh in world units.
+const MESH_SIZE: f32 = 5.5;
+
+// Last component of the unnormalized vertex normals.
+fn normal_depth(width: usize) -> f32 {
+    (4.0 * MESH_SIZE) / width as f32
+}
+
+pub fn height_map_mesh(params: &CellularSystemState) -> Mesh {
+    let height_map = height_map(params, MESH_SIZE);
+
+    let mut height_mesh = Mesh::new(bevy::render::render_resource::PrimitiveTopology::TriangleList);
+
+    height_mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, height_map.vertices);
+    height_mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, height_map.normals);
+    height_mesh.set_indices(Some(bevy::render::mesh::Indices::U32(height_map.indices)));
+    height_mesh
+}
+
+pub fn height_map(params: &CellularSystemState, size: f32) -> HeightMapMeshData {
+    let grid = &params.simulation.grid;
+    let (width, height) = (grid.width(), grid.height());
+    let mut vertices: Vec<[f32; 3]> = (0..width * height)
+        .map(|i| {
             [
-                size * ((i % params.map_size[0]) as f32 / (params.map_size[0] - 1) as f32 - 0.5),
-                0.5 * height_value as f32 / 255.0,
-                size * (params.map_size[1] as f32 / params.map_size[0] as f32)
-                    * ((i / params.map_size[0]) as f32 / (params.map_size[1] - 1) as f32 - 0.5),
+                size * ((i % width) as f32 / (width - 1) as f32 - 0.5),
+                0.0,
+                size * (height as f32 / width as f32)
+                    * ((i / width) as f32 / (height - 1) as f32 - 0.5),
             ]
-            .into()
         })
         .collect();
-    let indices = height_map_triangle_indices(params.map_size[0], params.map_size[1]);
-    let normals = calculate_normals(&vertices, params.map_size[0], params.map_size[1]);
+    write_heights(&mut vertices, grid, params.render_channel);
+    let indices = height_map_triangle_indices(width, height);
+    let mut normals = vec![[0.0; 3]; width * height];
+    write_normals(
+        &mut normals,
+        &vertices,
+        width,
+        height,
+        params.simulation.boundary,
+        normal_depth(width),
+    );
 
     HeightMapMeshData {
         vertices,
@@ -56,55 +77,51 @@ fn height_map_triangle_indices(width: usize, height: usize) -> Vec<u32> {
     indexlist
 }
 
-fn calculate_normals(vertices: &[Vec3], width: usize, height: usize) -> Vec<Vec3> {
-    let mut normalslist: Vec<Vec3> = vec![Vec3::default(); width * height];
-
-    for x in 0..(width as i32) {
-        for y in 0..(height as i32) {
-            let h_l =
-                vertices[torus_topology::modulo_robust(x - 1, width as i32) + width * y as usize].y;
-            let h_r =
-                vertices[torus_topology::modulo_robust(x + 1, width as i32) + width * y as usize].y;
-            let h_d = vertices
-                [x as usize + width * torus_topology::modulo_robust(y - 1, height as i32)]
-            .y;
-            let h_u = vertices
-                [x as usize + width * torus_topology::modulo_robust(y + 1, height as i32)]
-            .y;
-            normalslist[x as usize + width * y as usize] =
-                Vec3::new(h_l - h_r, h_d - h_u, (4.0 * 5.5) / 160.0).normalize();
-        }
-    }
-    normalslist
-}
-
 pub fn update_heightmap(
     mut meshes: ResMut<Assets<Mesh>>,
-    mesh: ResMut<super::state::HeightMapMesh>,
+    mut mesh: ResMut<super::state::HeightMapMesh>,
     params: Res<super::state::CellularSystemState>,
 ) {
+    let grid = &params.simulation.grid;
+    let size = (grid.width(), grid.height());
+    let rebuild = mesh.size != size;
+    if rebuild {
+        mesh.size = size;
+    }
     if let Some(id) = &mesh.mesh {
         let active_mesh = meshes.get_mut(id).unwrap();
-        let positions = active_mesh.attribute(Mesh::ATTRIBUTE_POSITION).unwrap();
-        if let bevy::render::mesh::VertexAttributeValues::Float32x3(vertices) = positions {
-            let new_vertices: Vec<Vec3> = vertices
-                .iter()
-                .enumerate()
-                .map(|(i, pos)| {
-                    let pixel = params.new_texture.pixels[i];
-                    let height_value = match params.render_channel {
-                        0 => pixel.r(),
-                        1 => pixel.g(),
-                        2 => pixel.b(),
-                        _ => ((pixel.r() as f32 + pixel.g() as f32 + pixel.b() as f32) / 3.0) as u8,
-                    };
-                    [pos[0], 0.5 * height_value as f32 / 255.0, pos[2]].into()
-                })
-                .collect();
-            let new_normals =
-                calculate_normals(&new_vertices, params.map_size[0], params.map_size[1]);
-            active_mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, new_vertices);
-            active_mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, new_normals);
+        if rebuild {
+            // The grid was resized, rebuild the mesh including its indices.
+            // Comparing the vertex count alone would miss e.g. 64x32 -> 32x64.
+            *active_mesh = height_map_mesh(&params);
+            return;
+        }
+        if let Some(VertexAttributeValues::Float32x3(vertices)) =
+            active_mesh.attribute_mut(Mesh::ATTRIBUTE_POSITION)
+        {
+            write_heights(vertices, grid, params.render_channel);
+        }
+        // The normals are taken out while the positions are read, then put
+        // back, so both buffers are reused.
+        let mut normals = active_mesh.remove_attribute(Mesh::ATTRIBUTE_NORMAL);
+        if let (
+            Some(VertexAttributeValues::Float32x3(normals)),
+            Some(VertexAttributeValues::Float32x3(vertices)),
+        ) = (
+            normals.as_mut(),
+            active_mesh.attribute(Mesh::ATTRIBUTE_POSITION),
+        ) {
+            write_normals(
+                normals,
+                vertices,
+                grid.width(),
+                grid.height(),
+                params.simulation.boundary,
+                normal_depth(grid.width()),
+            );
+        }
+        if let Some(normals) = normals {
+            active_mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
         }
     }
 }
@@ -1,262 +1,538 @@
-use bevy::prelude::*;
+use bevy::{
+    prelude::*,
+    render::texture::{CompressedImageFormats, ImageSampler, ImageType},
+};
 use bevy_egui::egui;
-use rand::prelude::Distribution;
-
-use super::torus_topology;
+use simulation::{
+    reaction, Automaton, CellKind, Domain, Flow, Grid, History, LifeRule, MapProfile,
+    ParameterTarget, ReactionModel, Simulation,
+};
+use std::sync::Arc;
 
 #[derive(Resource)]
 pub struct CellularSystemState {
-    pub texture_handle: String,
     pub iterating: bool,
-    iteration_in_buffer: u64,
     iterations_done: u64,
+    pub steps_per_frame: usize,
+    // Iterations left of a single step or run, taken while paused.
+    pub pending_steps: u64,
+    pub run_length: u64,
     pub texture: Option<egui::TextureHandle>,
-    pub new_texture: egui::ColorImage,
+    // Shared with the texture upload, filled in place once the upload is done.
+    pub image: Arc<egui::ColorImage>,
     pub painting: bool,
     pub paint_pos: egui::Pos2,
-    pub paint_color: [f32; 3],
+    pub paint_values: Vec<f32>,
     pub paint_radius: usize,
+    // Painted parameter map the brush draws into instead of the channels.
+    pub paint_map: Option<usize>,
+    pub map_paint_weight: f32,
+    // Dragging the brush pushes a fluid flow instead of painting.
+    pub stirring: bool,
+    pub stir_strength: f32,
+    pub map_image_path: String,
+    pub map_error: Option<String>,
+    // Cell kind the brush marks in the domain mask instead of painting.
+    pub domain_brush: Option<CellKind>,
+    pub domain_image_path: String,
+    pub domain_error: Option<String>,
+    pub flow_image_path: String,
+    pub flow_error: Option<String>,
     pub resetting: bool,
-    pub render_channel: usize,
-    pub map_size: [usize; 2],
+    pub render_channel: Option<usize>,
+    pub channel_colors: Vec<[f32; 3]>,
     pub canvas_size: [f32; 2],
+    pub grid_size: [usize; 2],
     pub fps: f64,
-    pub red: ChannelParameters,
-    pub green: ChannelParameters,
-    pub blue: ChannelParameters,
+    pub simulation: Simulation,
+    pub models: Vec<Arc<dyn ReactionModel>>,
+    pub equation_sources: Vec<String>,
+    pub equation_error: Option<String>,
+    pub life_rule_source: String,
+    pub life_rule_error: Option<String>,
+    pub instability: Option<String>,
+    pub history: History,
+    // Frame of the history on display while scrubbing, stepping is paused.
+    pub history_position: Option<usize>,
 }
 
 #[derive(Clone, Default, Resource)]
 pub struct HeightMapMesh {
     pub mesh: Option<Handle<Mesh>>,
-}
-
-#[derive(Clone, Default)]
-pub struct ChannelParameters {
-    pub diffusion_coefficient: f32,
-    pub growth_rate: f32,
-    pub interaction_coefficient: f32,
-    pub saturation_constant: f32,
-    pub feedback_coefficient: f32,
+    /// Grid width and height the mesh was built for.
+    pub size: (usize, usize),
 }
 
 impl CellularSystemState {
     pub fn paint(&mut self) {
-        let center_x = ((self.paint_pos.x * ((self.map_size[0] as f32) / self.canvas_size[0]))
-            as i32)
-            .clamp(0, (self.map_size[0] - 1) as i32);
-        let center_y = ((self.paint_pos.y * ((self.map_size[1] as f32) / self.canvas_size[1]))
-            as i32)
-            .clamp(0, (self.map_size[1] - 1) as i32);
-        let radius = self.paint_radius as i32;
-        for r in 0..2 * radius {
-            for s in 0..2 * radius {
-                if (r - radius) * (r - radius) + (s - radius) * (s - radius) <= radius * radius {
-                    self.new_texture[(
-                        torus_topology::modulo_robust(
-                            center_x + r - radius,
-                            self.map_size[0] as i32,
-                        ),
-                        torus_topology::modulo_robust(
-                            center_y + s - radius,
-                            self.map_size[1] as i32,
-                        ),
-                    )] = egui::Color32::from_rgb(
-                        (self.paint_color[0] * 255.0) as u8,
-                        (self.paint_color[1] * 255.0) as u8,
-                        (self.paint_color[2] * 255.0) as u8,
-                    );
+        let (center_x, center_y) = self.brush_cell();
+        let painted_map = self
+            .paint_map
+            .and_then(|index| self.simulation.parameter_maps.get_mut(index));
+        if let Some(MapProfile::Painted(weights)) = painted_map.map(|map| &mut map.profile) {
+            weights.paint(
+                center_x,
+                center_y,
+                self.paint_radius,
+                &[self.map_paint_weight],
+                self.simulation.boundary,
+            );
+            return;
+        }
+        if let Some(kind) = self.domain_brush {
+            self.simulation.paint_domain(
+                center_x,
+                center_y,
+                self.paint_radius,
+                kind,
+                &self.paint_values,
+            );
+            return;
+        }
+        // Life cells are painted alive and newborn, whatever the brush holds.
+        let values = match self.simulation.automaton() {
+            Automaton::Life(_) => &LifeRule::NEWBORN[..],
+            _ => &self.paint_values,
+        };
+        self.simulation
+            .paint(center_x, center_y, self.paint_radius, values);
+    }
+
+    /// Whether the brush stirs a fluid flow rather than painting.
+    pub fn stirs(&self) -> bool {
+        self.stirring && matches!(self.simulation.advection.flow, Flow::Fluid(_))
+    }
+
+    /// Pushes the fluid under the brush along a drag of `delta` canvas
+    /// pixels.
+    pub fn stir(&mut self, delta: egui::Vec2) {
+        let (center_x, center_y) = self.brush_cell();
+        let scale_x = self.simulation.grid.width() as f32 / self.canvas_size[0];
+        let scale_y = self.simulation.grid.height() as f32 / self.canvas_size[1];
+        let impulse = [
+            self.stir_strength * delta.x * scale_x,
+            self.stir_strength * delta.y * scale_y,
+        ];
+        self.simulation
+            .add_momentum(center_x, center_y, self.paint_radius, impulse);
+    }
+
+    // Grid cell under the brush.
+    fn brush_cell(&self) -> (i32, i32) {
+        let width = self.simulation.grid.width();
+        let height = self.simulation.grid.height();
+        let center_x = ((self.paint_pos.x * ((width as f32) / self.canvas_size[0])) as i32)
+            .clamp(0, (width - 1) as i32);
+        let center_y = ((self.paint_pos.y * ((height as f32) / self.canvas_size[1])) as i32)
+            .clamp(0, (height - 1) as i32);
+        (center_x, center_y)
+    }
+
+    /// Replaces the profile of a parameter map by the grey levels of the image
+    /// at `map_image_path`.
+    pub fn load_map_image(&mut self, index: usize) {
+        match read_grey_levels(&self.map_image_path) {
+            Ok(image) => {
+                self.map_error = None;
+                if let Some(map) = self.simulation.parameter_maps.get_mut(index) {
+                    map.profile = MapProfile::Image(image);
+                }
+            }
+            Err(error) => self.map_error = Some(error),
+        }
+    }
+
+    pub fn resize_grid(&mut self) {
+        let [width, height] = self.grid_size;
+        self.simulation.resize(width, height);
+        // Keep the canvas width and follow the aspect ratio of the grid.
+        self.canvas_size[1] = self.canvas_size[0] * height as f32 / width as f32;
+        self.refresh_texture();
+    }
+
+    pub fn iterations_done(&self) -> u64 {
+        self.iterations_done
+    }
+
+    /// Pauses and queues `steps` iterations.
+    pub fn run_steps(&mut self, steps: u64) {
+        self.resume_from_history(false);
+        self.iterating = false;
+        self.pending_steps = steps;
+    }
+
+    /// Shows frame `index` of the history, keeping the live state as the
+    /// newest frame to come back to.
+    pub fn scrub_to(&mut self, index: usize) {
+        let iteration = self.history.iteration(index);
+        if self.history_position.is_none() {
+            self.iterating = false;
+            let stride = std::mem::replace(&mut self.history.stride, 1);
+            self.history.record(self.iterations_done, &self.simulation);
+            self.history.stride = stride;
+        }
+        let Some(index) = self.history.position(iteration) else {
+            return;
+        };
+        if self.history.restore(index, &mut self.simulation).is_none() {
+            self.instability = Some("This frame has another channel count".to_owned());
+            return;
+        }
+        self.history_position = Some(index);
+        let grid = &self.simulation.grid;
+        if self.grid_size != [grid.width(), grid.height()] {
+            self.grid_size = [grid.width(), grid.height()];
+            self.canvas_size[1] = self.canvas_size[0] * grid.height() as f32 / grid.width() as f32;
+        }
+        self.refresh_texture();
+    }
+
+    /// Continues from the frame on display, dropping the frames after it. A
+    /// branch draws its noise from a new seed, so it takes another course.
+    pub fn resume_from_history(&mut self, branch: bool) {
+        let Some(index) = self.history_position.take() else {
+            return;
+        };
+        self.iterations_done = self.history.iteration(index);
+        self.history.truncate(index);
+        if branch {
+            self.simulation.set_seed(Simulation::random_seed());
+        }
+        self.instability = None;
+        self.iterating = true;
+    }
+
+    /// Redraws the image from the grid and updates the texture in place.
+    pub fn refresh_texture(&mut self) {
+        update_grid_image(
+            &self.simulation.grid,
+            &self.channel_colors,
+            Arc::make_mut(&mut self.image),
+        );
+        if let Some(texture) = &mut self.texture {
+            texture.set(self.image.clone(), Default::default());
+        }
+    }
+
+    /// Sets the flow to the image at `flow_image_path`, red and green giving
+    /// the horizontal and vertical velocity with 0.5 at rest.
+    pub fn load_flow_image(&mut self, scale: f32) {
+        match read_rgb(&self.flow_image_path) {
+            Ok(mut field) => {
+                self.flow_error = None;
+                for channel in 0..2 {
+                    for value in field.channel_mut(channel) {
+                        *value = 2.0 * *value - 1.0;
+                    }
                 }
+                self.simulation.advection.flow = Flow::Image { field, scale };
+            }
+            Err(error) => self.flow_error = Some(error),
+        }
+    }
+
+    /// Replaces the domain mask by the one drawn in the image at
+    /// `domain_image_path`.
+    pub fn load_domain_image(&mut self) {
+        match read_rgb(&self.domain_image_path) {
+            Ok(image) => {
+                self.domain_error = None;
+                let grid = &self.simulation.grid;
+                self.simulation.domain =
+                    Domain::from_image(&image, grid.width(), grid.height(), grid.channels());
+            }
+            Err(error) => self.domain_error = Some(error),
+        }
+    }
+
+    pub fn set_channel_count(&mut self, channels: usize) {
+        self.simulation.set_channel_count(channels);
+        self.sync_channel_count();
+    }
+
+    pub fn set_automaton(&mut self, automaton: Automaton) {
+        if let Automaton::Life(rule) = &automaton {
+            self.life_rule_source = rule.to_string();
+            self.life_rule_error = None;
+            self.resetting = true;
+        }
+        self.domain_brush = None;
+        self.simulation.set_automaton(automaton);
+        self.sync_channel_count();
+    }
+
+    pub fn apply_life_rule(&mut self) {
+        match LifeRule::parse(&self.life_rule_source) {
+            Ok(rule) => {
+                self.life_rule_error = None;
+                *self.simulation.automaton_mut() = Automaton::Life(rule);
             }
+            Err(error) => self.life_rule_error = Some(error.to_string()),
         }
     }
+
+    pub fn set_model(&mut self, model: Arc<dyn ReactionModel>) {
+        self.simulation.set_model(model);
+        self.paint_map = None;
+        self.sync_channel_count();
+        self.resetting = true;
+    }
+
+    pub fn apply_equations(&mut self) {
+        let model = match reaction::CustomModel::compile(&self.equation_sources) {
+            Ok(model) => model,
+            Err(error) => {
+                self.equation_error = Some(error.to_string());
+                return;
+            }
+        };
+        self.equation_error = None;
+        let previous_parameters: Vec<(String, f32)> = self
+            .simulation
+            .model()
+            .parameters()
+            .into_iter()
+            .map(|spec| spec.name)
+            .zip(self.simulation.parameters.iter().copied())
+            .collect();
+        let previous_channels = self.simulation.channels.clone();
+        let previous_maps = self.simulation.parameter_maps.clone();
+        let channel_count_changed = model.channel_count() != Some(self.simulation.channel_count());
+
+        self.simulation.set_model(Arc::new(model));
+        let specs = self.simulation.model().parameters();
+        for (spec, value) in specs.iter().zip(self.simulation.parameters.iter_mut()) {
+            if let Some((_, previous)) = previous_parameters
+                .iter()
+                .find(|(name, _)| *name == spec.name)
+            {
+                *value = *previous;
+            }
+        }
+        for (channel, previous) in self.simulation.channels.iter_mut().zip(previous_channels) {
+            channel.diffusion_coefficient = previous.diffusion_coefficient;
+            channel.noise = previous.noise;
+            channel.cross_diffusion = previous.cross_diffusion;
+        }
+        // Maps of shared parameters follow them by name.
+        self.simulation.parameter_maps = previous_maps
+            .into_iter()
+            .filter_map(|mut map| {
+                if let ParameterTarget::Global(index) = &mut map.target {
+                    let (name, _) = previous_parameters.get(*index)?;
+                    *index = specs.iter().position(|spec| spec.name == *name)?;
+                }
+                Some(map)
+            })
+            .collect();
+        self.paint_map = None;
+        self.sync_channel_count();
+        if channel_count_changed {
+            self.resetting = true;
+        }
+    }
+
+    pub fn channel_name(&self, channel: usize) -> String {
+        self.simulation
+            .channel_name(channel)
+            .unwrap_or_else(|| palette_channel_name(channel))
+    }
+
+    fn sync_channel_count(&mut self) {
+        let channels = self.simulation.channel_count();
+        self.paint_values.resize(channels, 1.0);
+        self.channel_colors = (0..channels)
+            .map(|channel| {
+                self.channel_colors
+                    .get(channel)
+                    .copied()
+                    .unwrap_or(default_channel_color(channel))
+            })
+            .collect();
+        if self
+            .render_channel
+            .is_some_and(|channel| channel >= channels)
+        {
+            self.render_channel = None;
+        }
+    }
+}
+
+const CHANNEL_PALETTE: [(&str, [f32; 3]); 6] = [
+    ("Red", [1.0, 0.0, 0.0]),
+    ("Green", [0.0, 1.0, 0.0]),
+    ("Blue", [0.0, 0.0, 1.0]),
+    ("Yellow", [1.0, 1.0, 0.0]),
+    ("Cyan", [0.0, 1.0, 1.0]),
+    ("Magenta", [1.0, 0.0, 1.0]),
+];
+
+fn palette_channel_name(channel: usize) -> String {
+    match CHANNEL_PALETTE.get(channel) {
+        Some((name, _)) => (*name).to_owned(),
+        None => format!("Channel {}", channel + 1),
+    }
+}
+
+fn default_channel_color(channel: usize) -> [f32; 3] {
+    CHANNEL_PALETTE[channel % CHANNEL_PALETTE.len()].1
 }
 
 impl Default for CellularSystemState {
     fn default() -> Self {
+        let simulation = Simulation::default();
+        let channel_colors: Vec<[f32; 3]> = (0..simulation.channel_count())
+            .map(default_channel_color)
+            .collect();
         Self {
-            texture_handle: "0".to_owned(),
-            iteration_in_buffer: 0,
             iterations_done: 0,
+            steps_per_frame: 1,
+            pending_steps: 0,
+            run_length: 100,
             texture: None,
-            new_texture: initial_system(160, 160),
+            image: Arc::new(grid_image(&simulation.grid, &channel_colors)),
             iterating: true,
             painting: false,
             paint_pos: [50.0, 50.0].into(),
-            paint_color: [1.0, 1.0, 1.0],
+            paint_values: vec![1.0; simulation.channel_count()],
             paint_radius: 20,
+            paint_map: None,
+            map_paint_weight: 1.0,
+            stirring: false,
+            stir_strength: 1.0,
+            map_image_path: String::new(),
+            map_error: None,
+            domain_brush: None,
+            domain_image_path: String::new(),
+            domain_error: None,
+            flow_image_path: String::new(),
+            flow_error: None,
             resetting: false,
-            render_channel: 3,
-            map_size: [160, 160],
+            render_channel: None,
+            channel_colors,
             canvas_size: [320.0, 320.0],
+            grid_size: [simulation.grid.width(), simulation.grid.height()],
             fps: 30.0,
-            red: ChannelParameters {
-                diffusion_coefficient: 0.02248,
-                growth_rate: 0.98204,
-                interaction_coefficient: 3.195,
-                saturation_constant: 0.112,
-                feedback_coefficient: 0.458,
-            },
-            green: ChannelParameters {
-                diffusion_coefficient: 0.08454,
-                growth_rate: 0.92762,
-                interaction_coefficient: 4.666,
-                saturation_constant: 0.62,
-                feedback_coefficient: 0.981,
-            },
-            blue: ChannelParameters {
-                diffusion_coefficient: 0.05918,
-                growth_rate: 0.92696,
-                interaction_coefficient: 2.015,
-                saturation_constant: 0.465,
-                feedback_coefficient: 0.727,
-            },
+            simulation,
+            models: reaction::builtin_models(),
+            equation_sources: vec![
+                "a*u*(1-u) - b*u*v/(1+c*u^2)".to_owned(),
+                "b*u*v/(1+c*u^2) - d*v".to_owned(),
+            ],
+            equation_error: None,
+            life_rule_source: LifeRule::default().to_string(),
+            life_rule_error: None,
+            instability: None,
+            history: History::default(),
+            history_position: None,
         }
     }
 }
 
-fn initial_system(width: usize, height: usize) -> egui::ColorImage {
-    let mut rng = rand::thread_rng();
-    let p = rand::distributions::Uniform::new_inclusive(0.0, 1.0);
-
-    let mut current_image =
-        egui::ColorImage::new([width, height], egui::Color32::from_rgb(128, 0, 50));
-    for x in 0..width {
-        for y in 0..height {
-            current_image[(x, y)] = egui::Color32::from_rgb(
-                (255.0 * p.sample(&mut rng) * (y as f32 / (height as f32))) as u8,
-                (255.0
-                    * p.sample(&mut rng)
-                    * ((x as f32) * (y as f32) / ((height as f32) * (width as f32))))
-                    as u8,
-                (255.0 * p.sample(&mut rng) * (x as f32 / (width as f32))) as u8,
-            );
-        }
+/// Whether images can be loaded from a path. A web page has no file system
+/// to read them from, so the image controls are hidden there.
+pub const CAN_READ_IMAGES: bool = !cfg!(target_arch = "wasm32");
+
+fn read_grey_levels(path: &str) -> Result<Grid, String> {
+    let rgb = read_rgb(path)?;
+    let mut grid = Grid::new(rgb.width(), rgb.height(), 1);
+    for (index, grey) in grid.channel_mut(0).iter_mut().enumerate() {
+        *grey = [0.2126, 0.7152, 0.0722]
+            .iter()
+            .enumerate()
+            .map(|(channel, weight)| weight * rgb.channel(channel)[index])
+            .sum();
     }
-    current_image
+    Ok(grid)
 }
 
-pub fn next_iteration(mut params: ResMut<CellularSystemState>) {
-    params.iteration_in_buffer += 1;
-    if (params.iteration_in_buffer > params.iterations_done && params.iterating)
-        || params.resetting
-        || params.painting
-    {
-        params.iterations_done += 1;
-        let mut new_image = egui::ColorImage::new(
-            [params.map_size[0], params.map_size[1]],
-            egui::Color32::from_rgb(
-                (params.iteration_in_buffer / 10) as u8,
-                params.iteration_in_buffer as u8,
-                50,
-            ),
-        );
-        if params.resetting {
-            new_image = initial_system(params.map_size[0], params.map_size[1]);
-            params.resetting = false;
-        } else {
-            for x in 0..params.map_size[0] {
-                for y in 0..params.map_size[1] {
-                    let r_sum_neighbours = torus_topology::sum_neighbour_channel(
-                        &params.new_texture,
-                        x as i32,
-                        y as i32,
-                        params.map_size[0] as i32,
-                        params.map_size[1] as i32,
-                        0,
-                    );
-
-                    let g_sum_neighbours = torus_topology::sum_neighbour_channel(
-                        &params.new_texture,
-                        x as i32,
-                        y as i32,
-                        params.map_size[0] as i32,
-                        params.map_size[1] as i32,
-                        1,
-                    );
-                    let b_sum_neighbours = torus_topology::sum_neighbour_channel(
-                        &params.new_texture,
-                        x as i32,
-                        y as i32,
-                        params.map_size[0] as i32,
-                        params.map_size[1] as i32,
-                        2,
-                    );
-                    let red = params.new_texture[(x, y)].to_array()[0] as f32;
-                    let green = params.new_texture[(x, y)].to_array()[1] as f32;
-                    let blue = params.new_texture[(x, y)].to_array()[2] as f32;
-
-                    let pixel_r =
-                        diffusion(red, r_sum_neighbours, params.red.diffusion_coefficient)
-                            + (255.0
-                                * reaction_red(red / 255.0, green / 255.0, blue / 255.0, &params));
-
-                    let pixel_g =
-                        diffusion(green, g_sum_neighbours, params.green.diffusion_coefficient)
-                            + (255.0
-                                * reaction_green(
-                                    red / 255.0,
-                                    green / 255.0,
-                                    blue / 255.0,
-                                    &params,
-                                ));
-
-                    let pixel_b =
-                        diffusion(blue, b_sum_neighbours, params.blue.diffusion_coefficient)
-                            + (255.0
-                                * reaction_blue(red / 255.0, green / 255.0, blue / 255.0, &params));
-
-                    new_image[(x, y)] =
-                        egui::Color32::from_rgb(pixel_r as u8, pixel_g as u8, pixel_b as u8);
-                }
-            }
+// Red, green and blue of an image file as three channels between 0 and 1.
+fn read_rgb(path: &str) -> Result<Grid, String> {
+    let bytes = std::fs::read(path).map_err(|error| format!("Cannot read {}: {}", path, error))?;
+    let extension = std::path::Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default();
+    let image = Image::from_buffer(
+        &bytes,
+        ImageType::Extension(extension),
+        CompressedImageFormats::NONE,
+        true,
+        ImageSampler::Default,
+    )
+    .map_err(|error| format!("Cannot decode {}: {}", path, error))?;
+    let rgb = image
+        .try_into_dynamic()
+        .map_err(|error| format!("Cannot convert {}: {}", path, error))?
+        .to_rgb32f();
+    let (width, height) = (rgb.width() as usize, rgb.height() as usize);
+    let mut grid = Grid::new(width, height, 3);
+    for (index, pixel) in rgb.as_raw().chunks(3).enumerate() {
+        for (channel, value) in pixel.iter().enumerate() {
+            grid.set(index % width, index / width, channel, *value);
         }
-
-        let strg = params.iteration_in_buffer.to_string();
-        params.new_texture = new_image;
-        params.texture_handle = strg;
-        let t: Option<egui::TextureHandle> = None;
-        params.texture = t;
     }
+    Ok(grid)
 }
 
-fn diffusion(concentration: f32, weighted_sum_neighbors: f32, diffusion_coefficient: f32) -> f32 {
-    concentration - 6.837 * concentration * diffusion_coefficient
-        + weighted_sum_neighbors * diffusion_coefficient
-}
-
-fn reaction_red(
-    red: f32,
-    green: f32,
-    blue: f32,
-    params: &bevy::prelude::ResMut<'_, CellularSystemState>,
-) -> f32 {
-    params.red.growth_rate * red * (1.0 - red)
-        - ((params.red.interaction_coefficient * red * green)
-            / (1.0 + params.red.saturation_constant * red * red))
-        + params.red.feedback_coefficient * (green - red) * blue * blue
+fn grid_image(grid: &Grid, channel_colors: &[[f32; 3]]) -> egui::ColorImage {
+    let mut image = egui::ColorImage::new([0, 0], egui::Color32::BLACK);
+    update_grid_image(grid, channel_colors, &mut image);
+    image
 }
 
-fn reaction_green(
-    red: f32,
-    green: f32,
-    blue: f32,
-    params: &bevy::prelude::ResMut<'_, CellularSystemState>,
-) -> f32 {
-    params.green.growth_rate * green * (1.0 - green)
-        - ((params.green.interaction_coefficient * green * blue)
-            / (1.0 + params.green.saturation_constant * green * green))
-        + params.green.feedback_coefficient * (blue - green) * red * red
+fn update_grid_image(grid: &Grid, channel_colors: &[[f32; 3]], image: &mut egui::ColorImage) {
+    let size = [grid.width(), grid.height()];
+    if image.size != size {
+        image.size = size;
+        image.pixels.resize(size[0] * size[1], egui::Color32::BLACK);
+    }
+    simulation::render::write_colors(
+        grid,
+        channel_colors,
+        &mut image.pixels,
+        egui::Color32::from_rgb,
+    );
 }
 
-fn reaction_blue(
-    red: f32,
-    green: f32,
-    blue: f32,
-    params: &bevy::prelude::ResMut<'_, CellularSystemState>,
-) -> f32 {
-    params.blue.growth_rate * blue * (1.0 - blue)
-        - ((params.blue.interaction_coefficient * blue * red)
-            / (1.0 + params.blue.saturation_constant * blue * blue))
-        + params.blue.feedback_coefficient * (red - blue) * green * green
+pub fn next_iteration(mut params: ResMut<CellularSystemState>) {
+    let params = params.as_mut();
+    if params.resetting {
+        // A reset starts a new run with its own history.
+        params.simulation.reset();
+        params.resetting = false;
+        params.instability = None;
+        params.history_position = None;
+        params.iterations_done = 0;
+        params.history.clear();
+        params.history.record(0, &params.simulation);
+        params.refresh_texture();
+        return;
+    }
+    if params.history_position.is_some() {
+        return;
+    }
+    let steps = if params.iterating {
+        params.steps_per_frame as u64
+    } else {
+        params.pending_steps.min(params.steps_per_frame as u64)
+    };
+    for _ in 0..steps {
+        params.simulation.step();
+        params.iterations_done += 1;
+        params.pending_steps = params.pending_steps.saturating_sub(1);
+        params
+            .history
+            .record(params.iterations_done, &params.simulation);
+        if let Err(instability) = params.simulation.check() {
+            params.iterating = false;
+            params.pending_steps = 0;
+            params.instability = Some(format!("Paused, the simulation blew up: {}", instability));
+            break;
+        }
+    }
+    // Painting shows up even while paused.
+    if steps > 0 || params.painting {
+        params.refresh_texture();
+    }
 }